@@ -1,16 +1,43 @@
-use std::fmt::Display;
+use std::{fmt::Display, sync::mpsc::Sender};
 
 use bincode::{Decode, Encode};
+use chrono::{DateTime, Local, Utc};
 use serde::{Deserialize, Serialize};
 
+use crate::{
+    config::{self, ActionCommand},
+    info::DaemonInfo,
+    scheduler::ActionTrigger,
+};
+
 #[derive(Serialize, Deserialize, Debug, Encode, Decode, PartialEq, Eq, Clone)]
 pub enum Action {
     Stop,
     Enable,
     Disable,
     Toggle,
+    /// Enable or disable the daemon, answered with the resulting state
+    SetEnabled(bool),
     ReloadConfig,
-    Trigger { action: String },
+    /// Load and validate the config file without applying it
+    CheckConfig,
+    /// Ask for the events and state as the daemon sees them
+    QueryInfo,
+    /// Use the `[automatic.profiles]` location of this name until the next reload, the
+    /// `[automatic]` one without a name
+    SetProfile(Option<String>),
+    Trigger {
+        trigger: ActionTrigger,
+        /// When the event was scheduled, earlier than now if it is caught up on
+        #[bincode(with_serde)]
+        at: DateTime<Utc>,
+        commands: Vec<ActionCommand>,
+    },
+    /// Sent by the reaper once a triggered action exited, `None` if it was killed by a signal
+    ActionFinished {
+        trigger: ActionTrigger,
+        exit_code: Option<i32>,
+    },
     Nothing,
 }
 
@@ -21,9 +48,82 @@ impl Display for Action {
             Action::Enable => f.write_str("Action - Enable"),
             Action::Disable => f.write_str("Action - Disable"),
             Action::Toggle => f.write_str("Action - Toggle"),
-            Action::ReloadConfig => f.write_str(&format!("Action - Relod ")),
-            Action::Trigger { action } => f.write_str(&format!("Action - Trigger - {}", action)),
+            Action::SetEnabled(enabled) => {
+                f.write_str(&format!("Action - Set enabled - {enabled}"))
+            }
+            Action::ReloadConfig => f.write_str("Action - Reload"),
+            Action::CheckConfig => f.write_str("Action - Check config"),
+            Action::QueryInfo => f.write_str("Action - Query info"),
+            Action::SetProfile(profile) => f.write_str(&format!(
+                "Action - Set profile - {}",
+                profile.as_deref().unwrap_or("none")
+            )),
+            Action::Trigger {
+                trigger, commands, ..
+            } => f.write_str(&format!(
+                "Action - Trigger - {trigger} - {}",
+                config::describe(commands).unwrap_or_default()
+            )),
+            Action::ActionFinished { trigger, exit_code } => f.write_str(&format!(
+                "Action - Finished - {trigger} - {}",
+                exit_code.map_or("killed".to_string(), |c| c.to_string())
+            )),
             Action::Nothing => f.write_str("Action -Nothing"),
         }
     }
 }
+
+impl Action {
+    /// Whether the daemon answers this action with a [`Response`]
+    pub fn expects_response(&self) -> bool {
+        matches!(
+            self,
+            Action::CheckConfig | Action::QueryInfo | Action::SetEnabled(_) | Action::SetProfile(_)
+        )
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Encode, Decode, PartialEq, Eq, Clone)]
+pub enum Response {
+    Done,
+    /// Whether the daemon runs actions after handling the request
+    Enabled(bool),
+    ConfigValid,
+    ConfigInvalid(String),
+    Info(DaemonInfo),
+    Error(String),
+}
+
+impl Display for Response {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Response::Done => f.write_str("done"),
+            Response::Enabled(true) => f.write_str("enabled"),
+            Response::Enabled(false) => f.write_str("disabled"),
+            Response::ConfigValid => f.write_str("Configuration is valid"),
+            Response::ConfigInvalid(reason) => {
+                f.write_str(&format!("Configuration is invalid: {reason}"))
+            }
+            Response::Info(info) => match &info.next_event {
+                Some(ev) => f.write_str(&format!("Next event:\n{}", ev.describe(info.now, &Local))),
+                None => f.write_str("No pending event"),
+            },
+            Response::Error(reason) => f.write_str(&format!("Daemon error: {reason}")),
+        }
+    }
+}
+
+/// An action on its way to the daemon's main loop, along with where to send the answer
+pub struct Request {
+    pub action: Action,
+    pub reply: Option<Sender<Response>>,
+}
+
+impl From<Action> for Request {
+    fn from(action: Action) -> Self {
+        Self {
+            action,
+            reply: None,
+        }
+    }
+}
@@ -0,0 +1,119 @@
+use std::{
+    fmt::Display,
+    fs::{self, OpenOptions},
+    io::Write,
+    path::Path,
+};
+
+use chrono::{DateTime, Local, NaiveDate, Utc};
+use serde::Serialize;
+
+use crate::scheduler::ActionTrigger;
+
+/// What became of a fired trigger
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case", tag = "outcome", content = "date")]
+pub enum Outcome {
+    Ran,
+    Disabled,
+    Blackout(NaiveDate),
+}
+
+/// A fired trigger as logged and appended to `audit_log`
+#[derive(Serialize, Debug)]
+pub struct TriggerRecord<'a> {
+    pub at: DateTime<Utc>,
+    pub trigger: ActionTrigger,
+    pub commands: Option<String>,
+    pub location: Option<&'a str>,
+    #[serde(flatten)]
+    pub outcome: Outcome,
+}
+
+impl Display for TriggerRecord<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&format!(
+            "{} at {} ({} local)",
+            self.trigger,
+            self.at.format("%Y-%m-%dT%H:%M:%SZ"),
+            self.at.with_timezone(&Local).format("%H:%M:%S")
+        ))?;
+        if let Some(location) = self.location {
+            f.write_str(&format!(" for {location}"))?;
+        }
+        let commands = self.commands.as_deref().unwrap_or("no command");
+        match self.outcome {
+            Outcome::Ran => f.write_str(&format!(": ran {commands}")),
+            Outcome::Disabled => f.write_str(&format!(": skipped {commands}, disabled")),
+            Outcome::Blackout(date) => {
+                f.write_str(&format!(": skipped {commands}, {date} is a blackout date"))
+            }
+        }
+    }
+}
+
+/// Appends `record` as a line of JSON to `path`. Once the file reaches `max_bytes` it is moved
+/// to `path.1`, replacing the previous one.
+pub fn append(path: &str, record: &TriggerRecord, max_bytes: u64) -> crate::error::Result<()> {
+    if fs::metadata(path).is_ok_and(|m| m.len() >= max_bytes) {
+        fs::rename(path, format!("{path}.1"))?;
+    }
+    let mut line = serde_json::to_string(record)?;
+    line.push('\n');
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(Path::new(path))?
+        .write_all(line.as_bytes())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{DateTime, NaiveDate};
+
+    use crate::scheduler::ActionTrigger;
+
+    use super::{Outcome, TriggerRecord, append};
+
+    fn record(outcome: Outcome) -> TriggerRecord<'static> {
+        TriggerRecord {
+            at: DateTime::from_timestamp(1752436361, 0).unwrap(),
+            trigger: ActionTrigger::Sunset,
+            commands: Some("hyprctl hyprsunset temperature 4500".to_string()),
+            location: Some("Berlin"),
+            outcome,
+        }
+    }
+
+    #[test]
+    fn record_states_whether_commands_ran() {
+        let ran = record(Outcome::Ran).to_string();
+        let blackout = record(Outcome::Blackout(
+            NaiveDate::from_ymd_opt(2025, 7, 13).unwrap(),
+        ))
+        .to_string();
+
+        assert!(ran.starts_with("sunset at 2025-07-13T19:52:41Z ("));
+        assert!(ran.ends_with(" for Berlin: ran hyprctl hyprsunset temperature 4500"));
+        assert!(blackout.ends_with(", 2025-07-13 is a blackout date"));
+    }
+
+    #[test]
+    fn append_rotates_full_log() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.log");
+        let path = path.to_str().unwrap();
+        let rotated = format!("{path}.1");
+
+        append(path, &record(Outcome::Ran), 100).unwrap();
+        append(path, &record(Outcome::Disabled), 100).unwrap();
+
+        let current = std::fs::read_to_string(path).unwrap();
+        let previous = std::fs::read_to_string(&rotated).unwrap();
+        let line: serde_json::Value = serde_json::from_str(current.trim()).unwrap();
+        assert_eq!(line["outcome"], "disabled");
+        assert_eq!(line["trigger"], "sunset");
+        assert_eq!(previous.lines().count(), 1);
+    }
+}
@@ -0,0 +1,50 @@
+use std::time::{Duration, Instant};
+
+use chrono::{TimeDelta, Utc};
+
+use crate::{
+    context::Context,
+    scheduler::{EventSource, TriggerSource},
+};
+
+pub struct Benchmark {
+    iterations: usize,
+}
+
+impl Benchmark {
+    pub fn new(iterations: usize) -> Self {
+        Self {
+            iterations: iterations.max(1),
+        }
+    }
+
+    pub fn run(self, context: Context) -> crate::error::Result<()> {
+        let source = TriggerSource::from_config(&context.config)?;
+        let start = Utc::now();
+        let mut samples = Vec::with_capacity(self.iterations);
+        for i in 0..self.iterations {
+            // spread the queried instants over a day so every interval branch gets exercised
+            let date = start + TimeDelta::seconds((i as i64 * 97) % 86_400);
+            let before = Instant::now();
+            std::hint::black_box(source.next_event_at(std::hint::black_box(date)));
+            samples.push(before.elapsed());
+        }
+        samples.sort();
+
+        let total: Duration = samples.iter().sum();
+        println!("iterations: {}", self.iterations);
+        println!("total:      {total:?}");
+        println!("average:    {:?}", total / self.iterations as u32);
+        println!("min:        {:?}", samples[0]);
+        println!("p50:        {:?}", percentile(&samples, 50));
+        println!("p90:        {:?}", percentile(&samples, 90));
+        println!("p99:        {:?}", percentile(&samples, 99));
+        println!("max:        {:?}", samples[samples.len() - 1]);
+        Ok(())
+    }
+}
+
+fn percentile(sorted: &[Duration], p: usize) -> Duration {
+    let index = (sorted.len() * p).div_ceil(100).saturating_sub(1);
+    sorted[index.min(sorted.len() - 1)]
+}
@@ -0,0 +1,347 @@
+/// Coordinates of major cities `location` in `[automatic]` can name, sorted by name
+static CITIES: &[(&str, f64, f64)] = &[
+    ("Abidjan", 5.36, -4.01),
+    ("Abu Dhabi", 24.45, 54.38),
+    ("Abuja", 9.08, 7.40),
+    ("Accra", 5.60, -0.19),
+    ("Addis Ababa", 9.03, 38.74),
+    ("Adelaide", -34.93, 138.60),
+    ("Ahmedabad", 23.02, 72.57),
+    ("Algiers", 36.75, 3.06),
+    ("Almaty", 43.24, 76.89),
+    ("Amman", 31.95, 35.93),
+    ("Amsterdam", 52.37, 4.90),
+    ("Anchorage", 61.22, -149.90),
+    ("Ankara", 39.93, 32.86),
+    ("Antwerp", 51.22, 4.40),
+    ("Asuncion", -25.26, -57.58),
+    ("Athens", 37.98, 23.73),
+    ("Atlanta", 33.75, -84.39),
+    ("Auckland", -36.85, 174.76),
+    ("Austin", 30.27, -97.74),
+    ("Baghdad", 33.31, 44.36),
+    ("Baku", 40.41, 49.87),
+    ("Baltimore", 39.29, -76.61),
+    ("Bamako", 12.64, -8.00),
+    ("Bangalore", 12.97, 77.59),
+    ("Bangkok", 13.76, 100.50),
+    ("Barcelona", 41.39, 2.17),
+    ("Basel", 47.56, 7.59),
+    ("Beijing", 39.90, 116.41),
+    ("Beirut", 33.89, 35.50),
+    ("Belfast", 54.60, -5.93),
+    ("Belgrade", 44.79, 20.45),
+    ("Belo Horizonte", -19.92, -43.94),
+    ("Bergen", 60.39, 5.32),
+    ("Berlin", 52.52, 13.40),
+    ("Bern", 46.95, 7.45),
+    ("Bilbao", 43.26, -2.93),
+    ("Birmingham", 52.49, -1.89),
+    ("Bogota", 4.71, -74.07),
+    ("Bologna", 44.49, 11.34),
+    ("Bordeaux", 44.84, -0.58),
+    ("Boston", 42.36, -71.06),
+    ("Brasilia", -15.79, -47.88),
+    ("Bratislava", 48.15, 17.11),
+    ("Bremen", 53.08, 8.80),
+    ("Brisbane", -27.47, 153.03),
+    ("Bristol", 51.45, -2.59),
+    ("Brno", 49.20, 16.61),
+    ("Brussels", 50.85, 4.35),
+    ("Bucharest", 44.43, 26.10),
+    ("Budapest", 47.50, 19.04),
+    ("Buenos Aires", -34.60, -58.38),
+    ("Cairo", 30.04, 31.24),
+    ("Calgary", 51.05, -114.07),
+    ("Cali", 3.45, -76.53),
+    ("Canberra", -35.28, 149.13),
+    ("Cape Town", -33.92, 18.42),
+    ("Caracas", 10.48, -66.90),
+    ("Cardiff", 51.48, -3.18),
+    ("Casablanca", 33.57, -7.59),
+    ("Chengdu", 30.57, 104.07),
+    ("Chennai", 13.08, 80.27),
+    ("Chicago", 41.88, -87.63),
+    ("Chongqing", 29.56, 106.55),
+    ("Christchurch", -43.53, 172.64),
+    ("Cincinnati", 39.10, -84.51),
+    ("Cleveland", 41.50, -81.69),
+    ("Cologne", 50.94, 6.96),
+    ("Colombo", 6.93, 79.85),
+    ("Copenhagen", 55.68, 12.57),
+    ("Cork", 51.90, -8.47),
+    ("Curitiba", -25.43, -49.27),
+    ("Dakar", 14.72, -17.47),
+    ("Dallas", 32.78, -96.80),
+    ("Damascus", 33.51, 36.29),
+    ("Dar es Salaam", -6.79, 39.21),
+    ("Darwin", -12.46, 130.84),
+    ("Delhi", 28.70, 77.10),
+    ("Denver", 39.74, -104.99),
+    ("Detroit", 42.33, -83.05),
+    ("Dhaka", 23.81, 90.41),
+    ("Doha", 25.29, 51.53),
+    ("Dortmund", 51.51, 7.47),
+    ("Dresden", 51.05, 13.74),
+    ("Dubai", 25.20, 55.27),
+    ("Dublin", 53.35, -6.26),
+    ("Durban", -29.86, 31.02),
+    ("Dusseldorf", 51.23, 6.77),
+    ("Edinburgh", 55.95, -3.19),
+    ("Edmonton", 53.55, -113.49),
+    ("Eindhoven", 51.44, 5.48),
+    ("Essen", 51.46, 7.01),
+    ("Florence", 43.77, 11.26),
+    ("Fortaleza", -3.73, -38.53),
+    ("Frankfurt", 50.11, 8.68),
+    ("Fukuoka", 33.59, 130.40),
+    ("Gdansk", 54.35, 18.65),
+    ("Geneva", 46.20, 6.14),
+    ("Genoa", 44.41, 8.93),
+    ("Ghent", 51.05, 3.72),
+    ("Glasgow", 55.86, -4.25),
+    ("Gothenburg", 57.71, 11.97),
+    ("Graz", 47.07, 15.44),
+    ("Guadalajara", 20.66, -103.35),
+    ("Guangzhou", 23.13, 113.26),
+    ("Guatemala City", 14.63, -90.51),
+    ("Hamburg", 53.55, 9.99),
+    ("Hanoi", 21.03, 105.85),
+    ("Hanover", 52.38, 9.73),
+    ("Harare", -17.83, 31.05),
+    ("Havana", 23.11, -82.37),
+    ("Helsinki", 60.17, 24.94),
+    ("Hiroshima", 34.39, 132.46),
+    ("Ho Chi Minh City", 10.82, 106.63),
+    ("Hobart", -42.88, 147.33),
+    ("Hong Kong", 22.32, 114.17),
+    ("Honolulu", 21.31, -157.86),
+    ("Houston", 29.76, -95.37),
+    ("Hyderabad", 17.39, 78.49),
+    ("Indianapolis", 39.77, -86.16),
+    ("Innsbruck", 47.27, 11.40),
+    ("Islamabad", 33.68, 73.05),
+    ("Istanbul", 41.01, 28.98),
+    ("Jakarta", -6.21, 106.85),
+    ("Jerusalem", 31.77, 35.21),
+    ("Johannesburg", -26.20, 28.05),
+    ("Kabul", 34.56, 69.21),
+    ("Kampala", 0.35, 32.58),
+    ("Karachi", 24.86, 67.01),
+    ("Kathmandu", 27.72, 85.32),
+    ("Kazan", 55.80, 49.11),
+    ("Khartoum", 15.50, 32.56),
+    ("Kiev", 50.45, 30.52),
+    ("Kigali", -1.95, 30.06),
+    ("Kingston", 17.97, -76.79),
+    ("Kinshasa", -4.44, 15.27),
+    ("Kolkata", 22.57, 88.36),
+    ("Krakow", 50.06, 19.94),
+    ("Kuala Lumpur", 3.139, 101.69),
+    ("Kuwait City", 29.38, 47.99),
+    ("Kyoto", 35.01, 135.77),
+    ("La Paz", -16.49, -68.12),
+    ("Lagos", 6.52, 3.38),
+    ("Lahore", 31.55, 74.34),
+    ("Las Vegas", 36.17, -115.14),
+    ("Leeds", 53.80, -1.55),
+    ("Leipzig", 51.34, 12.37),
+    ("Lille", 50.63, 3.06),
+    ("Lima", -12.05, -77.04),
+    ("Linz", 48.31, 14.29),
+    ("Lisbon", 38.72, -9.14),
+    ("Liverpool", 53.41, -2.98),
+    ("Ljubljana", 46.06, 14.51),
+    ("Lodz", 51.76, 19.46),
+    ("London", 51.51, -0.13),
+    ("Los Angeles", 34.05, -118.24),
+    ("Luanda", -8.84, 13.23),
+    ("Lusaka", -15.39, 28.32),
+    ("Luxembourg", 49.61, 6.13),
+    ("Lyon", 45.76, 4.84),
+    ("Madrid", 40.42, -3.70),
+    ("Malaga", 36.72, -4.42),
+    ("Malmo", 55.60, 13.00),
+    ("Managua", 12.11, -86.24),
+    ("Manchester", 53.48, -2.24),
+    ("Manila", 14.60, 120.98),
+    ("Maputo", -25.97, 32.57),
+    ("Marseille", 43.30, 5.37),
+    ("Mecca", 21.39, 39.86),
+    ("Medellin", 6.24, -75.58),
+    ("Melbourne", -37.81, 144.96),
+    ("Mexico City", 19.43, -99.13),
+    ("Miami", 25.76, -80.19),
+    ("Milan", 45.46, 9.19),
+    ("Milwaukee", 43.04, -87.91),
+    ("Minneapolis", 44.98, -93.27),
+    ("Minsk", 53.90, 27.56),
+    ("Mogadishu", 2.05, 45.32),
+    ("Monterrey", 25.69, -100.32),
+    ("Montevideo", -34.90, -56.16),
+    ("Montreal", 45.50, -73.57),
+    ("Moscow", 55.76, 37.62),
+    ("Mumbai", 19.08, 72.88),
+    ("Munich", 48.14, 11.58),
+    ("Murmansk", 68.97, 33.08),
+    ("Muscat", 23.59, 58.41),
+    ("Nagoya", 35.18, 136.91),
+    ("Nairobi", -1.29, 36.82),
+    ("Nantes", 47.22, -1.55),
+    ("Naples", 40.85, 14.27),
+    ("Nashville", 36.16, -86.78),
+    ("New Orleans", 29.95, -90.07),
+    ("New York", 40.71, -74.01),
+    ("Nice", 43.70, 7.27),
+    ("Nicosia", 35.19, 33.38),
+    ("Nuremberg", 49.45, 11.08),
+    ("Osaka", 34.69, 135.50),
+    ("Oslo", 59.91, 10.75),
+    ("Ottawa", 45.42, -75.70),
+    ("Oulu", 65.01, 25.47),
+    ("Palermo", 38.12, 13.36),
+    ("Panama City", 8.98, -79.52),
+    ("Paris", 48.86, 2.35),
+    ("Perth", -31.95, 115.86),
+    ("Philadelphia", 39.95, -75.17),
+    ("Phnom Penh", 11.56, 104.93),
+    ("Phoenix", 33.45, -112.07),
+    ("Pittsburgh", 40.44, -80.00),
+    ("Portland", 45.52, -122.68),
+    ("Porto", 41.16, -8.63),
+    ("Porto Alegre", -30.03, -51.23),
+    ("Prague", 50.08, 14.44),
+    ("Pretoria", -25.75, 28.19),
+    ("Pyongyang", 39.04, 125.76),
+    ("Quebec City", 46.81, -71.21),
+    ("Quito", -0.18, -78.47),
+    ("Rabat", 34.02, -6.83),
+    ("Recife", -8.05, -34.88),
+    ("Reykjavik", 64.15, -21.94),
+    ("Riga", 56.95, 24.11),
+    ("Rio de Janeiro", -22.91, -43.17),
+    ("Riyadh", 24.71, 46.68),
+    ("Rome", 41.90, 12.50),
+    ("Rotterdam", 51.92, 4.48),
+    ("Rovaniemi", 66.50, 25.73),
+    ("Sacramento", 38.58, -121.49),
+    ("Saint Petersburg", 59.93, 30.36),
+    ("Salt Lake City", 40.76, -111.89),
+    ("Salvador", -12.97, -38.50),
+    ("Salzburg", 47.81, 13.06),
+    ("San Antonio", 29.42, -98.49),
+    ("San Diego", 32.72, -117.16),
+    ("San Francisco", 37.77, -122.42),
+    ("San Jose", 37.34, -121.89),
+    ("San Juan", 18.47, -66.11),
+    ("Santiago", -33.45, -70.67),
+    ("Santo Domingo", 18.49, -69.93),
+    ("Sao Paulo", -23.55, -46.63),
+    ("Sapporo", 43.06, 141.35),
+    ("Sarajevo", 43.86, 18.41),
+    ("Seattle", 47.61, -122.33),
+    ("Seoul", 37.57, 126.98),
+    ("Seville", 37.39, -5.98),
+    ("Shanghai", 31.23, 121.47),
+    ("Shenzhen", 22.54, 114.06),
+    ("Singapore", 1.35, 103.82),
+    ("Skopje", 42.00, 21.43),
+    ("Sofia", 42.70, 23.32),
+    ("St. Louis", 38.63, -90.20),
+    ("Stavanger", 58.97, 5.73),
+    ("Stockholm", 59.33, 18.07),
+    ("Strasbourg", 48.57, 7.75),
+    ("Stuttgart", 48.78, 9.18),
+    ("Sydney", -33.87, 151.21),
+    ("Taipei", 25.03, 121.57),
+    ("Tallinn", 59.44, 24.75),
+    ("Tampa", 27.95, -82.46),
+    ("Tashkent", 41.30, 69.24),
+    ("Tbilisi", 41.72, 44.79),
+    ("Tehran", 35.69, 51.39),
+    ("Tel Aviv", 32.09, 34.78),
+    ("The Hague", 52.08, 4.30),
+    ("Thessaloniki", 40.64, 22.94),
+    ("Tokyo", 35.68, 139.69),
+    ("Toronto", 43.65, -79.38),
+    ("Toulouse", 43.60, 1.44),
+    ("Tromso", 69.65, 18.96),
+    ("Trondheim", 63.43, 10.40),
+    ("Tunis", 36.81, 10.18),
+    ("Turin", 45.07, 7.69),
+    ("Ulaanbaatar", 47.89, 106.91),
+    ("Utrecht", 52.09, 5.12),
+    ("Valencia", 39.47, -0.38),
+    ("Valletta", 35.90, 14.51),
+    ("Vancouver", 49.28, -123.12),
+    ("Venice", 45.44, 12.32),
+    ("Vienna", 48.21, 16.37),
+    ("Vilnius", 54.69, 25.28),
+    ("Warsaw", 52.23, 21.01),
+    ("Washington", 38.91, -77.04),
+    ("Wellington", -41.29, 174.78),
+    ("Winnipeg", 49.90, -97.14),
+    ("Wroclaw", 51.11, 17.04),
+    ("Yerevan", 40.18, 44.51),
+    ("Yokohama", 35.44, 139.64),
+    ("Zagreb", 45.82, 15.98),
+    ("Zurich", 47.38, 8.54),
+];
+
+/// Lowercase letters and digits only, so `new-york` and `New York` match
+fn normalize(name: &str) -> String {
+    name.chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+/// Latitude and longitude of the city `name`
+pub fn lookup(name: &str) -> Option<(f64, f64)> {
+    let name = normalize(name);
+    CITIES
+        .iter()
+        .find(|(city, ..)| normalize(city) == name)
+        .map(|(_, latitude, longitude)| (*latitude, *longitude))
+}
+
+/// Up to 10 known cities starting like `name`, to suggest when it is unknown
+pub fn similar(name: &str) -> Vec<&'static str> {
+    let name = normalize(name);
+    for len in (1..=name.len().min(3)).rev() {
+        let found = CITIES
+            .iter()
+            .map(|(city, ..)| *city)
+            .filter(|city| normalize(city).starts_with(&name[..len]))
+            .take(10)
+            .collect::<Vec<_>>();
+        if !found.is_empty() {
+            return found;
+        }
+    }
+    vec![]
+}
+
+#[cfg(test)]
+mod test {
+    use super::{CITIES, lookup, similar};
+
+    #[test]
+    fn lookup_ignores_case_and_separators() {
+        assert_eq!(lookup("Berlin"), Some((52.52, 13.40)));
+        assert_eq!(lookup("new-york"), lookup("New York"));
+        assert!(lookup("Atlantis").is_none());
+    }
+
+    #[test]
+    fn unknown_city_suggests_similar_names() {
+        assert_eq!(similar("Berln"), ["Bergen", "Berlin", "Bern"]);
+    }
+
+    #[test]
+    fn coordinates_are_in_range() {
+        for (city, latitude, longitude) in CITIES {
+            assert!(latitude.abs() <= 90.0 && longitude.abs() <= 180.0, "{city}");
+        }
+    }
+}
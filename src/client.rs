@@ -0,0 +1,85 @@
+use std::{fmt::Display, io, path::PathBuf};
+
+use interprocess::local_socket::{GenericNamespaced, Name, Stream, ToNsName, traits::Stream as _};
+
+use crate::{
+    actions::{Action, Response},
+    config::SOCKET_NAME,
+};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum DaemonStatus {
+    Running,
+    /// The socket exists but nobody accepts connections on it
+    Stale,
+    NotRunning,
+}
+
+impl Display for DaemonStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DaemonStatus::Running => f.write_str("running"),
+            DaemonStatus::Stale => f.write_str("stale socket"),
+            DaemonStatus::NotRunning => f.write_str("not running"),
+        }
+    }
+}
+
+pub fn socket_name() -> io::Result<Name<'static>> {
+    SOCKET_NAME.to_ns_name::<GenericNamespaced>()
+}
+
+/// The file interprocess binds the socket name to where there is no abstract namespace
+#[cfg(target_os = "macos")]
+pub fn socket_file() -> PathBuf {
+    // there is no /run/user on macOS
+    PathBuf::from("/tmp").join(SOCKET_NAME)
+}
+
+/// The file interprocess binds the socket name to where there is no abstract namespace
+#[cfg(all(unix, not(target_os = "macos")))]
+pub fn socket_file() -> PathBuf {
+    let run_user = PathBuf::from(format!("/run/user/{}", nix::unistd::getuid()));
+    if run_user.exists() {
+        run_user.join(SOCKET_NAME)
+    } else {
+        PathBuf::from("/tmp").join(SOCKET_NAME)
+    }
+}
+
+/// Probes the daemon socket with a connection that is closed again without sending anything
+pub fn daemon_status() -> DaemonStatus {
+    let Ok(name) = socket_name() else {
+        return DaemonStatus::NotRunning;
+    };
+    match Stream::connect(name) {
+        Ok(_) => DaemonStatus::Running,
+        // on Linux the name lives in the abstract namespace and vanishes with its owner,
+        // elsewhere a refused connection means the socket file was left behind
+        Err(err)
+            if err.kind() == io::ErrorKind::ConnectionRefused
+                && !cfg!(any(target_os = "linux", target_os = "android")) =>
+        {
+            DaemonStatus::Stale
+        }
+        Err(_) => DaemonStatus::NotRunning,
+    }
+}
+
+pub fn daemon_running() -> bool {
+    daemon_status() == DaemonStatus::Running
+}
+
+/// Sends `action` to the running daemon, waiting for its answer if the action has one
+pub fn send(action: Action) -> crate::error::Result<Response> {
+    let name = socket_name()?;
+    let mut stream = Stream::connect(name).map_err(|_| crate::error::Error::DaemonNotRunning)?;
+    bincode::encode_into_std_write(&action, &mut stream, bincode::config::standard())?;
+    if !action.expects_response() {
+        return Ok(Response::Done);
+    }
+    Ok(bincode::decode_from_std_read(
+        &mut stream,
+        bincode::config::standard(),
+    )?)
+}
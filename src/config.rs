@@ -1,14 +1,23 @@
-use std::fmt::Display;
+use std::{collections::BTreeMap, fmt::Display, str::FromStr, time::Duration};
 
-use chrono::NaiveTime;
+use bincode::{Decode, Encode};
+use chrono::{
+    DateTime, Datelike, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta, Utc, Weekday,
+};
+use chrono_tz::Tz;
 use figment::{
-    Figment,
-    providers::{Format, Serialized, Toml},
+    Figment, Provider, Source,
+    providers::{Env, Format, Serialized, Toml},
+    value::Value,
 };
 use serde::{Deserialize, Serialize};
-use toml_edit::ser::to_string_pretty;
+use sunrise::DawnType;
+use toml_edit::{DocumentMut, ser::to_string_pretty};
 
-use super::scheduler::ActionTrigger;
+use super::{
+    cities, geoclue,
+    scheduler::{ActionTrigger, LocationInfo, TriggerSource},
+};
 pub static SOCKET_NAME: &str = "hyprsunrisewatcher.sock";
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -18,30 +27,572 @@ pub struct Configuration {
     pub automatic: Option<AutomaticConfig>,
     pub actions: Actions,
     pub hot_reload: bool,
+    /// Run the action of the current period when the daemon starts
+    pub run_on_start: bool,
+    /// Run the action of the last event missed while suspended once the clock jumps ahead
+    pub catch_up_on_resume: bool,
+    /// Wait for triggered actions to exit so failures can be reported
+    pub wait_for_action: bool,
+    /// Log the output and exit status of actions instead of discarding the output
+    pub log_actions: bool,
+    /// Show a desktop notification whenever actions run
+    pub notify_on_trigger: bool,
+    /// Kill actions still running after this many seconds, unlimited if unset
+    pub action_timeout_secs: Option<u64>,
+    /// How often an unreadable config file is retried on reload
+    pub reload_retries: u32,
+    pub reload_retry_delay_ms: u64,
+    /// Changes to the config file less than this apart cause a single reload
+    pub reload_debounce_ms: u64,
+    /// Prometheus textfile the daemon keeps up to date, disabled if unset
+    pub metrics_file: Option<String>,
+    /// Every fired trigger is appended to this file as a line of JSON, disabled if unset
+    pub audit_log: Option<String>,
+    /// Size at which `audit_log` is moved aside to `<audit_log>.1`
+    pub audit_log_max_bytes: u64,
+    /// Where the daemon writes its PID while it runs
+    pub pid_file: String,
+    /// Dates on which no actions are fired at all
+    pub blackout_dates: Vec<BlackoutDate>,
+    /// Run actions through a login shell so profile setup applies, costs a profile read per action
+    pub login_shell: bool,
+    /// Program actions are run with
+    pub shell: String,
+    /// Arguments passed to `shell` before the action, `-c` or `-lc` with `login_shell` if unset,
+    /// `/C` on Windows
+    pub shell_args: Option<Vec<String>>,
+    /// Outputs actions apply to, every monitor reported by Hyprland if empty
+    pub outputs: Vec<String>,
+    /// Command that has to accept the new config file before a reload is applied
+    pub reload_validator: Option<String>,
+    pub reload_validator_timeout_secs: u64,
+    /// Wait for running actions to exit before a reload or shutdown is carried out
+    pub drain_actions: bool,
+    pub drain_timeout_secs: u64,
+    /// Where day and night start for the two state classification of `is-dark`
+    pub day_boundary: DayBoundary,
+    pub night_boundary: NightBoundary,
+    /// Seconds to wait after a resume before catching up on missed actions, 0 runs them at once
+    pub resume_delay_seconds: u64,
 }
 
 impl Configuration {
     pub const DEFAULT_PATH: &str = "~/.config/hyprsunrisewatcher/config.toml";
+    /// Environment variables starting with this override the file, `__` separates nested keys
+    pub const ENV_PREFIX: &str = "HYPRSUNRISE_";
 
     pub fn load_default() -> crate::error::Result<Configuration> {
         Self::load(Self::DEFAULT_PATH)
     }
 
-    pub fn load(path: &str) -> crate::error::Result<Configuration> {
-        let figment = Figment::new()
+    /// The overrides of the process environment, see `ENV_PREFIX`
+    fn env() -> Env {
+        Env::prefixed(Self::ENV_PREFIX).split("__")
+    }
+
+    fn figment(path: &str, env: impl Provider) -> Figment {
+        Figment::new()
             .merge(Serialized::defaults(Configuration::default()))
-            .merge(Toml::file(&path));
+            .merge(Toml::file(path))
+            .merge(env)
+    }
 
-        let config: Configuration = figment.extract()?;
+    /// Loads `path` over the defaults, a missing file leaves them as they are
+    pub fn load(path: &str) -> crate::error::Result<Configuration> {
+        Self::load_with_env(path, Self::env())
+    }
+
+    /// Like `load`, with `env` in place of the process environment
+    fn load_with_env(path: &str, env: impl Provider) -> crate::error::Result<Configuration> {
+        // figment would read nothing from a directory and quietly use the defaults
+        if std::fs::metadata(path).is_ok_and(|metadata| !metadata.is_file()) {
+            return Err(crate::error::Error::InvalidPath(
+                "config".to_string(),
+                format!("{path} is not a file"),
+            ));
+        }
+        let mut config: Configuration = Self::figment(path, env).extract()?;
+        config.expand_paths()?;
 
         Ok(config)
     }
+
+    /// Parses `toml` over the defaults without reading a file or the environment
+    #[cfg(test)]
+    pub fn parse(toml: &str) -> Configuration {
+        Figment::new()
+            .merge(Serialized::defaults(Configuration::default()))
+            .merge(Toml::string(toml))
+            .extract()
+            .expect("document should parse")
+    }
+
+    /// Like `load`, but a missing file is an error instead of the defaults
+    pub fn load_existing(path: &str) -> crate::error::Result<Configuration> {
+        if !std::path::Path::new(path).exists() {
+            let resolved = std::path::absolute(path)
+                .map_or_else(|_| path.to_string(), |p| p.display().to_string());
+            return Err(crate::error::Error::ConfigNotFound(resolved));
+        }
+        Self::load(path)
+    }
+
+    /// The file actually read for `path` and which fields it or the environment sets
+    pub fn sources(path: &str) -> crate::error::Result<ConfigSources> {
+        Self::sources_with_env(path, Self::env())
+    }
+
+    /// Like `sources`, with `env` in place of the process environment
+    fn sources_with_env(path: &str, env: impl Provider) -> crate::error::Result<ConfigSources> {
+        let figment = Self::figment(path, env);
+        let file = figment
+            .metadata()
+            .find_map(|metadata| match &metadata.source {
+                Some(Source::File(file)) => Some(file.clone()),
+                _ => None,
+            });
+        let mut fields = BTreeMap::new();
+        collect_origins(&figment, &figment.extract::<Value>()?, "", &mut fields);
+        Ok(ConfigSources {
+            path: file
+                .clone()
+                .or_else(|| std::path::absolute(path).ok())
+                .map(|p| p.display().to_string())
+                .unwrap_or_else(|| path.to_string()),
+            found: file.is_some(),
+            fields,
+        })
+    }
+
+    /// Exactly one of `manual` and `automatic` has to be set.
+    ///
+    /// The default `manual` without time stamps doesn't conflict with `automatic`, as nothing of
+    /// it would be ignored.
+    fn check_schedule(&self) -> crate::error::Result<()> {
+        match (&self.manual, &self.automatic) {
+            (Some(manual), Some(_)) if !manual.time_stamps.is_empty() => {
+                Err(crate::error::Error::InvalidConfiguration(
+                    "both [manual] time stamps and [automatic] are set, remove one of them"
+                        .to_string(),
+                ))
+            }
+            (None, None) => Err(crate::error::Error::InvalidConfiguration(
+                "neither [manual] nor [automatic] is set".to_string(),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Semantic checks beyond what deserialization already catches, the daemon refuses to start
+    /// or reload with a configuration failing them
+    pub fn validate(&self) -> crate::error::Result<()> {
+        if self.shell.trim().is_empty() {
+            return Err(crate::error::Error::InvalidConfiguration(
+                "shell is empty".to_string(),
+            ));
+        }
+        self.check_schedule()?;
+        for m in self.manual.iter().flat_map(|manual| &manual.time_stamps) {
+            match (&m.name, &m.command) {
+                (Some(name), Some(_)) => {
+                    return Err(crate::error::Error::InvalidAction(format!(
+                        "{name}, a time stamp can't have both a name and a command"
+                    )));
+                }
+                (Some(name), None) if !self.actions.named.contains_key(name) => {
+                    return Err(crate::error::Error::InvalidAction(format!(
+                        "{name}, not defined in [actions.named]"
+                    )));
+                }
+                _ => {}
+            }
+        }
+        TriggerSource::from_config(self)?;
+        for profile in self
+            .automatic
+            .iter()
+            .flat_map(|auto| auto.profiles.values())
+        {
+            LocationInfo::try_from(resolve_coordinates(
+                profile.latitude,
+                profile.longitude,
+                &profile.location,
+            )?)?;
+        }
+        Ok(())
+    }
+
+    pub fn to_toml(&self) -> crate::error::Result<String> {
+        to_string_pretty(self).map_err(|err| {
+            crate::error::Error::Serialization("configuration".to_string(), err.to_string())
+        })
+    }
+
+    /// What the trigger thread reads of the configuration
+    pub fn trigger_settings(&self) -> TriggerSettings<'_> {
+        TriggerSettings {
+            automatic: &self.automatic,
+            manual: &self.manual,
+            actions: &self.actions,
+            catch_up_on_resume: self.catch_up_on_resume,
+            resume_delay_seconds: self.resume_delay_seconds,
+        }
+    }
+
+    /// Whether the trigger thread has to be handed `other`. GeoClue may report another location
+    /// on every reload.
+    pub fn trigger_thread_differs(&self, other: &Configuration) -> bool {
+        self.trigger_settings() != other.trigger_settings()
+            || other
+                .automatic
+                .as_ref()
+                .is_some_and(|auto| auto.source == LocationSource::Geoclue)
+    }
+
+    /// Name of the configured location, if any, the `location` city unless a `name` is given
+    pub fn location_name(&self) -> Option<&str> {
+        self.automatic.as_ref()?.location_name()
+    }
+
+    /// Switches `[automatic]` to the location `profile`, back to its own without one
+    pub fn set_profile(&mut self, profile: Option<String>) -> crate::error::Result<()> {
+        let Some(auto) = &mut self.automatic else {
+            return Err(crate::error::Error::InvalidConfiguration(
+                "profiles need an [automatic] location".to_string(),
+            ));
+        };
+        let previous = std::mem::replace(&mut auto.active_profile, profile);
+        if let Err(err) = auto.active() {
+            auto.active_profile = previous;
+            return Err(err);
+        }
+        Ok(())
+    }
+
+    /// Two state classification of the period started by `current`
+    pub fn is_dark(&self, current: ActionTrigger) -> bool {
+        match current {
+            ActionTrigger::Dawn => self.day_boundary == DayBoundary::Sunrise,
+            ActionTrigger::Sunrise => false,
+            ActionTrigger::Sunset => self.night_boundary == NightBoundary::Sunset,
+            ActionTrigger::Dusk => true,
+            ActionTrigger::SolarNoon => false,
+            ActionTrigger::SolarMidnight => true,
+        }
+    }
+
+    pub fn action_timeout(&self) -> Option<Duration> {
+        self.action_timeout_secs.map(Duration::from_secs)
+    }
+
+    pub fn reload_debounce(&self) -> Duration {
+        Duration::from_millis(self.reload_debounce_ms)
+    }
+
+    pub fn is_blackout(&self, date: NaiveDate) -> bool {
+        self.blackout_dates.iter().any(|b| b.matches(date))
+    }
+
+    fn expand_paths(&mut self) -> crate::error::Result<()> {
+        if let Some(path) = &self.metrics_file {
+            self.metrics_file = Some(expand_path("metrics_file", path)?);
+        }
+        if let Some(path) = &self.audit_log {
+            self.audit_log = Some(expand_path("audit_log", path)?);
+        }
+        self.pid_file = expand_path("pid_file", &self.pid_file)?;
+        if let Some(auto) = &mut self.automatic
+            && let Some(path) = &auto.event_cache
+        {
+            auto.event_cache = Some(expand_path("automatic.event_cache", path)?);
+        }
+        Ok(())
+    }
+
+    /// The default configuration annotated with commented-out examples for every field.
+    ///
+    /// Lines starting with `#` directly followed by a key or table header are examples
+    /// that can be uncommented as is.
+    pub fn default_document() -> crate::error::Result<DocumentMut> {
+        let mut doc: DocumentMut = Configuration::default().to_toml()?.parse()?;
+        let root = doc.as_table_mut();
+        root.decor_mut()
+            .set_prefix("# Generated by `hyprsunrisewatcher print-default-config`\n\n");
+        if let Some(mut key) = root.key_mut("enabled") {
+            key.leaf_decor_mut()
+                .set_prefix("# Whether actions are fired at all\n");
+        }
+        if let Some(mut key) = root.key_mut("hot_reload") {
+            key.leaf_decor_mut()
+                .set_prefix("# Reload this file automatically whenever it changes\n");
+        }
+        if let Some(mut key) = root.key_mut("run_on_start") {
+            key.leaf_decor_mut().set_prefix(concat!(
+                "# Run the action of the current period on start, e.g. the sunset action when started\n",
+                "# in the evening, instead of waiting for the next event\n",
+            ));
+        }
+        if let Some(mut key) = root.key_mut("catch_up_on_resume") {
+            key.leaf_decor_mut().set_prefix(concat!(
+                "# Run the action of the last event that passed while suspended on resume, e.g. the\n",
+                "# dusk action after sleeping through sunset and dusk, `resume_delay_seconds` later.\n",
+                "# Skipped events aren't run otherwise\n",
+            ));
+        }
+        if let Some(mut key) = root.key_mut("wait_for_action") {
+            key.leaf_decor_mut().set_prefix(
+                "# Wait for actions to exit and run `on_action_failure` if they fail\n",
+            );
+        }
+        if let Some(mut key) = root.key_mut("log_actions") {
+            key.leaf_decor_mut().set_prefix(
+                "# Log what actions print along with their exit status, detached actions excepted\n",
+            );
+        }
+        if let Some(mut key) = root.key_mut("notify_on_trigger") {
+            key.leaf_decor_mut().set_prefix(
+                "# Confirm every event that ran actions with a desktop notification through notify-send\n",
+            );
+        }
+        if let Some(mut key) = root.key_mut("reload_retries") {
+            key.leaf_decor_mut().set_prefix(
+                "# Retries while the file is unreadable on reload, e.g. while an editor saves it\n",
+            );
+        }
+        if let Some(mut key) = root.key_mut("reload_debounce_ms") {
+            key.leaf_decor_mut().set_prefix(
+                "# Editors write several times per save, changes this close together reload once\n",
+            );
+        }
+        if let Some(mut key) = root.key_mut("pid_file") {
+            key.leaf_decor_mut()
+                .set_prefix("# Written while the daemon runs, `status` reports the PID from it\n");
+        }
+        if let Some(mut key) = root.key_mut("blackout_dates") {
+            key.leaf_decor_mut().set_prefix(concat!(
+                "# Dates without any actions, either once (\"2025-12-24\") ",
+                "or every year (\"12-24\")\n",
+            ));
+        }
+        if let Some(mut key) = root.key_mut("login_shell") {
+            key.leaf_decor_mut().set_prefix(concat!(
+                "# Run actions with `sh -lc` so variables exported by ~/.profile are available,\n",
+                "# at the cost of reading the profile for every action\n",
+            ));
+        }
+        if let Some(mut key) = root.key_mut("shell") {
+            key.leaf_decor_mut().set_prefix(concat!(
+                "# Program running the actions, e.g. \"bash\" for bash-isms. `shell_args` replaces the\n",
+                "# default `-c` (`-lc` with `login_shell`) passed before the action\n",
+                "#shell_args = [\"-O\", \"extglob\", \"-c\"]\n",
+            ));
+        }
+        if let Some(mut key) = root.key_mut("reload_validator_timeout_secs") {
+            key.leaf_decor_mut()
+                .set_prefix("# Seconds `reload_validator` may run before the reload is rejected\n");
+        }
+        if let Some(mut key) = root.key_mut("drain_actions") {
+            key.leaf_decor_mut().set_prefix(concat!(
+                "# Let running actions exit before reloading or stopping, so an action of the old\n",
+                "# configuration can't race the new one. Gives up after `drain_timeout_secs`\n",
+            ));
+        }
+        if let Some(mut key) = root.key_mut("day_boundary") {
+            key.leaf_decor_mut().set_prefix(concat!(
+                "# Events starting the day (\"dawn\" or \"sunrise\") and the night (\"sunset\" or \"dusk\")\n",
+                "# for `is-dark`, the four triggers are unaffected\n",
+            ));
+        }
+        if let Some(mut key) = root.key_mut("resume_delay_seconds") {
+            key.leaf_decor_mut().set_prefix(concat!(
+                "# Give the compositor time to come back after a resume before catching up on\n",
+                "# missed actions, 0 runs them immediately\n",
+            ));
+        }
+        if let Some(mut key) = root.key_mut("outputs") {
+            key.leaf_decor_mut().set_prefix(concat!(
+                "# Outputs passed to actions as HSW_OUTPUTS, an action containing `{output}` runs once\n",
+                "# per output with the name substituted. Queried from hyprctl if empty\n",
+            ));
+        }
+        if let Some(manual) = root.get_mut("manual").and_then(|m| m.as_table_mut()) {
+            // the examples below define the time stamps as an array of tables
+            manual.remove("time_stamps");
+            // examples for optional top level keys have to come before the first table
+            manual.decor_mut().set_prefix(concat!(
+                "# Prometheus textfile updated on every trigger and state change\n",
+                "#metrics_file = \"/var/lib/node_exporter/textfile/hyprsunrisewatcher.prom\"\n",
+                "# Append every fired trigger and whether its actions ran, moved aside to\n",
+                "# `<file>.1` once it reaches `audit_log_max_bytes`\n",
+                "#audit_log = \"~/.local/state/hyprsunrisewatcher/audit.log\"\n",
+                "# Run with the path of the changed file before a reload, a nonzero exit keeps\n",
+                "# the current configuration\n",
+                "#reload_validator = \"~/.config/hyprsunrisewatcher/validate.sh\"\n",
+                "# Kill actions that are still running after this many seconds\n",
+                "#action_timeout_secs = 300\n",
+                "\n# Fire actions at fixed times of day, used when [automatic] is absent\n",
+            ));
+        }
+        if let Some(actions) = root.get_mut("actions").and_then(|a| a.as_table_mut()) {
+            actions.decor_mut().set_prefix(concat!(
+                "# Time zone of the time stamps, the system's by default\n",
+                "#timezone = \"Europe/Berlin\"\n",
+                "#\n",
+                "#[[manual.time_stamps]]\n",
+                "#trigger_time = \"07:00:00\"\n",
+                "#action = \"sunrise\"\n",
+                "#\n",
+                "#[[manual.time_stamps]]\n",
+                "#trigger_time = \"21:00:00\"\n",
+                "#action = \"sunset\"\n",
+                "# Runs the `[actions.named]` entry instead of `on_dusk`\n",
+                "#[[manual.time_stamps]]\n",
+                "#trigger_time = \"22:30:00\"\n",
+                "#action = \"dusk\"\n",
+                "#name = \"night_mode\"\n",
+                "# Or give the commands right here, and only on some days instead of every day\n",
+                "#[[manual.time_stamps]]\n",
+                "#trigger_time = \"23:30:00\"\n",
+                "#action = \"dusk\"\n",
+                "#command = \"hyprctl dispatch dpms off\"\n",
+                "#days = [\"Sun\", \"Mon\", \"Tue\", \"Wed\", \"Thu\"]\n",
+                "\n",
+                "# Commands run through `sh -c` when the corresponding event fires\n",
+            ));
+        }
+        doc.set_trailing(concat!(
+            "#on_dawn = \"hyprctl hyprsunset temperature 5000\"\n",
+            "#on_sunrise = \"hyprctl hyprsunset identity\"\n",
+            "#on_sunset = \"hyprctl hyprsunset temperature 4500\"\n",
+            "#on_dusk = \"hyprctl hyprsunset temperature 3500\"\n",
+            "# The sun's highest point, and 12 hours later its lowest, only scheduled if set\n",
+            "#on_solar_noon = \"notify-send \\\"Solar noon\\\"\"\n",
+            "#on_solar_midnight = \"notify-send \\\"Solar midnight\\\"\"\n",
+            "# Actions can also be given as `{ command = \"...\", detach = true }` to keep them\n",
+            "# running when the daemon stops, or as a list of commands that are run in order\n",
+            "# Receives HSW_TRIGGER and HSW_EXIT_CODE of the failed action\n",
+            "#on_action_failure = \"notify-send \\\"$HSW_TRIGGER action failed\\\"\"\n",
+            "# Commands manual time stamps refer to by `name`\n",
+            "#[actions.named]\n",
+            "#night_mode = \"hyprctl keyword decoration:dim_inactive true\"\n",
+            "\n",
+            "# Compute the events from the position of the sun instead of the [manual] time stamps\n",
+            "#[automatic]\n",
+            "#name = \"Berlin\"\n",
+            "#latitude = 52.52\n",
+            "#longitude = 13.405\n",
+            "# Instead of latitude and longitude, the name of a major city to take them from\n",
+            "#location = \"Berlin\"\n",
+            "# Ask GeoClue where the device is on start and reload, the above is the fallback\n",
+            "#source = \"geoclue\"\n",
+            "# Dawn and dusk at civil (default), nautical or astronomical twilight\n",
+            "#twilight = \"nautical\"\n",
+            "# Minutes to move events by, e.g. to dim the screen before the sun is gone\n",
+            "#offsets = { sunset = -15 }\n",
+            "# Local times for days without the event, e.g. no sunset during polar day\n",
+            "#fallback = { sunrise = \"07:00\", sunset = \"21:00\" }\n",
+            "# Written by `hyprsunrisewatcher precompute`, missing dates are computed live\n",
+            "#event_cache = \"~/.cache/hyprsunrisewatcher/events.json\"\n",
+            "# Used instead of the location above while active, switch with\n",
+            "# `hyprsunrisewatcher profile cabin` and back with `hyprsunrisewatcher profile`\n",
+            "#active_profile = \"cabin\"\n",
+            "#[automatic.profiles.cabin]\n",
+            "#name = \"Summer cabin\"\n",
+            "#latitude = 61.1\n",
+            "#longitude = 10.5\n",
+        ));
+        Ok(doc)
+    }
+}
+
+/// `commands` joined for display, `None` if there are none
+pub fn describe(commands: &[ActionCommand]) -> Option<String> {
+    (!commands.is_empty()).then(|| {
+        commands
+            .iter()
+            .map(ActionCommand::command)
+            .collect::<Vec<_>>()
+            .join("; ")
+    })
+}
+
+/// Where a configuration field got its value from
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Origin {
+    Default,
+    File,
+    Environment,
+}
+
+/// The config file that was read and the origin of every field, keyed by dotted path
+#[derive(Serialize, Debug, Default, PartialEq, Eq)]
+pub struct ConfigSources {
+    pub path: String,
+    /// Without a file every field is a default
+    pub found: bool,
+    pub fields: BTreeMap<String, Origin>,
+}
+
+impl ConfigSources {
+    /// Dotted paths of the fields that came from `origin`
+    pub fn fields_from(&self, origin: Origin) -> Vec<&str> {
+        self.fields
+            .iter()
+            .filter(|(_, o)| **o == origin)
+            .map(|(field, _)| field.as_str())
+            .collect()
+    }
+}
+
+fn collect_origins(
+    figment: &Figment,
+    value: &Value,
+    prefix: &str,
+    fields: &mut BTreeMap<String, Origin>,
+) {
+    match value {
+        Value::Dict(_, dict) if !dict.is_empty() => {
+            for (key, value) in dict {
+                let path = match prefix {
+                    "" => key.clone(),
+                    _ => format!("{prefix}.{key}"),
+                };
+                collect_origins(figment, value, &path, fields);
+            }
+        }
+        _ => {
+            let origin = match figment.get_metadata(value.tag()).map(|m| &m.source) {
+                Some(Some(Source::File(_))) => Origin::File,
+                Some(Some(Source::Code(_))) | None => Origin::Default,
+                Some(_) => Origin::Environment,
+            };
+            fields.insert(prefix.to_string(), origin);
+        }
+    }
+}
+
+/// Expands `~` and environment variables in the path-valued setting `field`
+pub fn expand_path(field: &str, path: &str) -> crate::error::Result<String> {
+    match shellexpand::full(path) {
+        Ok(expanded) => Ok(expanded.into_owned()),
+        Err(err) => Err(crate::error::Error::InvalidPath(
+            field.to_string(),
+            err.to_string(),
+        )),
+    }
 }
 
 impl Display for Configuration {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let serialized = to_string_pretty(self).map_err(|_| std::fmt::Error)?;
-        f.write_str(&serialized)
+        match self.to_toml() {
+            Ok(serialized) => f.write_str(&serialized),
+            Err(err) => {
+                // fmt::Error can't carry the cause, callers that care should use `to_toml`
+                log::error!("{err}");
+                Err(std::fmt::Error)
+            }
+        }
     }
 }
 
@@ -51,45 +602,946 @@ impl Default for Configuration {
             enabled: true,
             manual: Some(ManualConfig {
                 time_stamps: vec![],
+                timezone: None,
             }),
             automatic: None,
             actions: Actions::default(),
             hot_reload: false,
+            run_on_start: false,
+            catch_up_on_resume: false,
+            wait_for_action: false,
+            log_actions: false,
+            notify_on_trigger: false,
+            action_timeout_secs: None,
+            reload_retries: 5,
+            reload_retry_delay_ms: 100,
+            reload_debounce_ms: 500,
+            metrics_file: None,
+            audit_log: None,
+            audit_log_max_bytes: 1024 * 1024,
+            pid_file: if cfg!(windows) {
+                "${TEMP}/hyprsunrisewatcher.pid"
+            } else {
+                "${XDG_RUNTIME_DIR:-/tmp}/hyprsunrisewatcher.pid"
+            }
+            .to_string(),
+            blackout_dates: vec![],
+            login_shell: false,
+            shell: if cfg!(windows) { "cmd" } else { "sh" }.to_string(),
+            shell_args: None,
+            outputs: vec![],
+            reload_validator: None,
+            reload_validator_timeout_secs: 10,
+            drain_actions: false,
+            drain_timeout_secs: 30,
+            day_boundary: DayBoundary::Sunrise,
+            night_boundary: NightBoundary::Sunset,
+            resume_delay_seconds: 3,
         }
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+/// The event starting the day
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DayBoundary {
+    Dawn,
+    Sunrise,
+}
+
+/// The event starting the night
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum NightBoundary {
+    Sunset,
+    Dusk,
+}
+
+/// How the coordinates of `[automatic]` are found
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LocationSource {
+    /// `latitude` and `longitude` or `location`
+    #[default]
+    Config,
+    /// The device location from GeoClue, looked up on start and reload
+    Geoclue,
+}
+
+/// The twilight whose start and end are dawn and dusk
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Twilight {
+    /// The sun is 6° below the horizon
+    #[default]
+    Civil,
+    /// The sun is 12° below the horizon
+    Nautical,
+    /// The sun is 18° below the horizon
+    Astronomical,
+}
+
+impl Display for Twilight {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Twilight::Civil => f.write_str("civil"),
+            Twilight::Nautical => f.write_str("nautical"),
+            Twilight::Astronomical => f.write_str("astronomical"),
+        }
+    }
+}
+
+impl From<Twilight> for DawnType {
+    fn from(value: Twilight) -> Self {
+        match value {
+            Twilight::Civil => DawnType::Civil,
+            Twilight::Nautical => DawnType::Nautical,
+            Twilight::Astronomical => DawnType::Astronomical,
+        }
+    }
+}
+
+/// A date on which nothing fires, `YYYY-MM-DD` for a single day or `MM-DD` for every year
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(try_from = "String", into = "String")]
+pub enum BlackoutDate {
+    Once(NaiveDate),
+    Yearly { month: u32, day: u32 },
+}
+
+impl BlackoutDate {
+    pub fn matches(&self, date: NaiveDate) -> bool {
+        match *self {
+            BlackoutDate::Once(d) => d == date,
+            BlackoutDate::Yearly { month, day } => date.month() == month && date.day() == day,
+        }
+    }
+}
+
+impl FromStr for BlackoutDate {
+    type Err = crate::error::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(date) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+            return Ok(BlackoutDate::Once(date));
+        }
+        // parse against a leap year so that 02-29 is accepted
+        NaiveDate::parse_from_str(&format!("2000-{s}"), "%Y-%m-%d")
+            .map(|d| BlackoutDate::Yearly {
+                month: d.month(),
+                day: d.day(),
+            })
+            .map_err(|_| crate::error::Error::InvalidDate(s.to_string()))
+    }
+}
+
+impl TryFrom<String> for BlackoutDate {
+    type Error = crate::error::Error;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<BlackoutDate> for String {
+    fn from(value: BlackoutDate) -> Self {
+        value.to_string()
+    }
+}
+
+impl Display for BlackoutDate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BlackoutDate::Once(date) => date.format("%Y-%m-%d").fmt(f),
+            BlackoutDate::Yearly { month, day } => f.write_str(&format!("{month:02}-{day:02}")),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct ManualTimeStamp {
     pub trigger_time: NaiveTime,
+    /// The period the time stamp starts, e.g. for `is-dark`
     pub action: ActionTrigger,
+    /// Runs the commands of this entry in `[actions.named]` instead of those of `action`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Runs these commands instead of those of `action`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command: Option<ActionCommands>,
+    /// The weekdays the time stamp applies on, every day if empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub days: Vec<Weekday>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+impl ManualTimeStamp {
+    /// Whether the time stamp applies on `date`
+    pub fn applies_on(&self, date: NaiveDate) -> bool {
+        self.days.is_empty() || self.days.contains(&date.weekday())
+    }
+
+    /// The commands this time stamp runs, looked up in `actions` unless given inline
+    pub fn commands<'a>(&'a self, actions: &'a Actions) -> &'a [ActionCommand] {
+        match (&self.command, &self.name) {
+            (Some(command), _) => command.as_slice(),
+            (None, Some(name)) => actions.named(name),
+            (None, None) => actions.get(self.action),
+        }
+    }
+}
+
+/// The fields of a [`Configuration`] the trigger thread reads, a reload only reaches the thread
+/// if one of them changed. The thread reads them through this, so it can't miss a change.
+#[derive(Debug, PartialEq)]
+pub struct TriggerSettings<'a> {
+    pub automatic: &'a Option<AutomaticConfig>,
+    pub manual: &'a Option<ManualConfig>,
+    pub actions: &'a Actions,
+    pub catch_up_on_resume: bool,
+    pub resume_delay_seconds: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct ManualConfig {
+    #[serde(default)]
     pub time_stamps: Vec<ManualTimeStamp>,
+    /// IANA name of the zone the time stamps are in, e.g. `Europe/Berlin`, local time if unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<Tz>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+impl ManualConfig {
+    /// `at` on the clock of the time stamps
+    pub fn wall_clock(&self, at: DateTime<Utc>) -> NaiveDateTime {
+        match self.timezone {
+            Some(tz) => at.with_timezone(&tz).naive_local(),
+            None => at.with_timezone(&Local).naive_local(),
+        }
+    }
+
+    /// The instant the clock of the time stamps shows `time` on `date`. `None` if the clock skips
+    /// it as daylight saving time starts, the first of both if it shows it twice as it ends.
+    pub fn instant(&self, date: NaiveDate, time: NaiveTime) -> Option<DateTime<Utc>> {
+        let wall_clock = date.and_time(time);
+        match self.timezone {
+            Some(tz) => wall_clock
+                .and_local_timezone(tz)
+                .earliest()
+                .map(|at| at.with_timezone(&Utc)),
+            None => wall_clock
+                .and_local_timezone(Local)
+                .earliest()
+                .map(|at| at.with_timezone(&Utc)),
+        }
+    }
+
+    /// The time stamps applying on `day` with the instants they fire at, leaving out those the
+    /// clock skips
+    pub fn on(&self, day: NaiveDate) -> impl Iterator<Item = (&ManualTimeStamp, DateTime<Utc>)> {
+        self.time_stamps
+            .iter()
+            .filter(move |m| m.applies_on(day))
+            .filter_map(move |m| Some((m, self.instant(day, m.trigger_time)?)))
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct AutomaticConfig {
-    pub longitude: f64,
-    pub latitude: f64,
+    /// Display name of the location, shown in logs and passed to actions as `HSW_LOCATION`
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub longitude: Option<f64>,
+    #[serde(default)]
+    pub latitude: Option<f64>,
+    /// City the coordinates are taken from unless `latitude` and `longitude` are given
+    #[serde(default)]
+    pub location: Option<String>,
+    /// Where the coordinates come from, the configured ones are the fallback for `geoclue`
+    #[serde(default)]
+    pub source: LocationSource,
+    /// Events written by `precompute`, used instead of live computation for the covered dates
+    #[serde(default)]
+    pub event_cache: Option<String>,
+    /// How far the sun is below the horizon at dawn and dusk
+    #[serde(default)]
+    pub twilight: Twilight,
+    /// Minutes the events are moved by
+    #[serde(default)]
+    pub offsets: Offsets,
+    /// Local times used on days the sun doesn't reach an event's elevation, e.g. in polar regions
+    #[serde(default)]
+    pub fallback: Fallback,
+    /// Other locations by name, e.g. a second home, used instead of the above while active
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, LocationProfile>,
+    /// The entry of `profiles` to use, switched at runtime with the `profile` command
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub active_profile: Option<String>,
+}
+
+/// A location of `[automatic.profiles]`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LocationProfile {
+    /// Display name, the name of the profile if unset
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub longitude: Option<f64>,
+    #[serde(default)]
+    pub latitude: Option<f64>,
+    #[serde(default)]
+    pub location: Option<String>,
+}
+
+impl AutomaticConfig {
+    /// The coordinates GeoClue reports with `source = "geoclue"`, otherwise or if it can't tell
+    /// the configured [`coordinates`](Self::coordinates). Only the daemon looks the device up.
+    pub fn located_coordinates(&self) -> crate::error::Result<(f64, f64)> {
+        if self.source == LocationSource::Geoclue
+            && let Some(coordinates) = geoclue::locate()
+        {
+            return Ok(coordinates);
+        }
+        self.coordinates()
+    }
+
+    /// The coordinates of the active profile or of `[automatic]` itself: `latitude` and
+    /// `longitude`, or the coordinates of the `location` city without them
+    pub fn coordinates(&self) -> crate::error::Result<(f64, f64)> {
+        match self.active()? {
+            Some(profile) => {
+                resolve_coordinates(profile.latitude, profile.longitude, &profile.location)
+            }
+            None => resolve_coordinates(self.latitude, self.longitude, &self.location),
+        }
+    }
+
+    /// Name of the active profile or of `[automatic]` itself, the `location` city unless a `name`
+    /// is given
+    pub fn location_name(&self) -> Option<&str> {
+        if let Ok(Some(profile)) = self.active() {
+            return profile
+                .name
+                .as_deref()
+                .or(profile.location.as_deref())
+                .or(self.active_profile.as_deref());
+        }
+        self.name.as_deref().or(self.location.as_deref())
+    }
+
+    /// The active profile, `None` if there is none
+    pub fn active(&self) -> crate::error::Result<Option<&LocationProfile>> {
+        let Some(name) = &self.active_profile else {
+            return Ok(None);
+        };
+        self.profiles.get(name).map(Some).ok_or_else(|| {
+            crate::error::Error::InvalidConfiguration(format!(
+                "unknown profile {name}, configured are: {}",
+                self.profiles.keys().cloned().collect::<Vec<_>>().join(", ")
+            ))
+        })
+    }
 }
-#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+
+fn resolve_coordinates(
+    latitude: Option<f64>,
+    longitude: Option<f64>,
+    location: &Option<String>,
+) -> crate::error::Result<(f64, f64)> {
+    match (latitude, longitude, location) {
+        (Some(latitude), Some(longitude), _) => Ok((latitude, longitude)),
+        (None, None, Some(location)) => cities::lookup(location).ok_or_else(|| {
+            crate::error::Error::InvalidConfiguration(format!(
+                "unknown location {location}, known cities starting alike: {}",
+                cities::similar(location).join(", ")
+            ))
+        }),
+        _ => Err(crate::error::Error::InvalidConfiguration(
+            "[automatic] needs either latitude and longitude or a location".to_string(),
+        )),
+    }
+}
+
+/// Minutes each event is moved by, negative values fire earlier
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(default)]
+pub struct Offsets {
+    pub dawn: i64,
+    pub sunrise: i64,
+    pub sunset: i64,
+    pub dusk: i64,
+    pub solar_noon: i64,
+    pub solar_midnight: i64,
+}
+
+/// Local times of day standing in for events that don't happen, unset ones are skipped
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(default)]
+pub struct Fallback {
+    pub dawn: Option<NaiveTime>,
+    pub sunrise: Option<NaiveTime>,
+    pub sunset: Option<NaiveTime>,
+    pub dusk: Option<NaiveTime>,
+}
+
+impl Offsets {
+    pub fn get(&self, trigger: ActionTrigger) -> TimeDelta {
+        TimeDelta::minutes(match trigger {
+            ActionTrigger::Dawn => self.dawn,
+            ActionTrigger::Sunrise => self.sunrise,
+            ActionTrigger::Sunset => self.sunset,
+            ActionTrigger::Dusk => self.dusk,
+            ActionTrigger::SolarNoon => self.solar_noon,
+            ActionTrigger::SolarMidnight => self.solar_midnight,
+        })
+    }
+}
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
 pub struct Actions {
-    on_sunrise: Option<String>,
-    on_sunset: Option<String>,
-    on_dawn: Option<String>,
-    on_dusk: Option<String>,
+    on_sunrise: Option<ActionCommands>,
+    on_sunset: Option<ActionCommands>,
+    on_dawn: Option<ActionCommands>,
+    on_dusk: Option<ActionCommands>,
+    on_solar_noon: Option<ActionCommands>,
+    on_solar_midnight: Option<ActionCommands>,
+    on_action_failure: Option<String>,
+    /// Commands manual time stamps refer to by `name`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    named: BTreeMap<String, ActionCommands>,
+}
+
+/// A command, either given as a plain string or as a table with further options
+#[derive(Serialize, Deserialize, Encode, Decode, Debug, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum ActionCommand {
+    Command(String),
+    Detailed {
+        command: String,
+        /// Let the command outlive the daemon
+        #[serde(default)]
+        detach: bool,
+    },
+}
+
+impl ActionCommand {
+    pub fn command(&self) -> &str {
+        match self {
+            ActionCommand::Command(command) | ActionCommand::Detailed { command, .. } => command,
+        }
+    }
+
+    pub fn detach(&self) -> bool {
+        matches!(self, ActionCommand::Detailed { detach: true, .. })
+    }
+}
+
+/// The commands of one trigger, a single command or a list of them run in order
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum ActionCommands {
+    One(ActionCommand),
+    Many(Vec<ActionCommand>),
+}
+
+impl ActionCommands {
+    pub fn as_slice(&self) -> &[ActionCommand] {
+        match self {
+            ActionCommands::One(command) => std::slice::from_ref(command),
+            ActionCommands::Many(commands) => commands,
+        }
+    }
 }
 
 impl Actions {
-    pub fn get(&self, trigger: ActionTrigger) -> Option<String> {
-        match trigger {
-            ActionTrigger::Sunrise => self.on_sunrise.clone(),
-            ActionTrigger::Sunset => self.on_sunset.clone(),
-            ActionTrigger::Dusk => self.on_dusk.clone(),
-            ActionTrigger::Dawn => self.on_dawn.clone(),
+    /// The commands of `trigger` in the order they are run
+    pub fn get(&self, trigger: ActionTrigger) -> &[ActionCommand] {
+        let commands = match trigger {
+            ActionTrigger::Sunrise => &self.on_sunrise,
+            ActionTrigger::Sunset => &self.on_sunset,
+            ActionTrigger::Dusk => &self.on_dusk,
+            ActionTrigger::Dawn => &self.on_dawn,
+            ActionTrigger::SolarNoon => &self.on_solar_noon,
+            ActionTrigger::SolarMidnight => &self.on_solar_midnight,
+        };
+        commands.as_ref().map_or(&[], ActionCommands::as_slice)
+    }
+
+    /// Whether no event runs any command, `on_action_failure` doesn't count
+    pub fn is_empty(&self) -> bool {
+        [
+            ActionTrigger::Dawn,
+            ActionTrigger::Sunrise,
+            ActionTrigger::SolarNoon,
+            ActionTrigger::Sunset,
+            ActionTrigger::Dusk,
+            ActionTrigger::SolarMidnight,
+        ]
+        .into_iter()
+        .all(|trigger| self.get(trigger).is_empty())
+            && self.named.is_empty()
+    }
+
+    /// The commands of the `[actions.named]` entry `name`, empty if there is none
+    pub fn named(&self, name: &str) -> &[ActionCommand] {
+        self.named.get(name).map_or(&[], ActionCommands::as_slice)
+    }
+
+    /// The commands of `trigger` joined for display, `None` if there are none
+    pub fn describe(&self, trigger: ActionTrigger) -> Option<String> {
+        describe(self.get(trigger))
+    }
+
+    /// All configured commands together with the name of their field
+    pub fn commands(&self) -> Vec<(String, &str)> {
+        [
+            ("on_sunrise", ActionTrigger::Sunrise),
+            ("on_sunset", ActionTrigger::Sunset),
+            ("on_dawn", ActionTrigger::Dawn),
+            ("on_dusk", ActionTrigger::Dusk),
+            ("on_solar_noon", ActionTrigger::SolarNoon),
+            ("on_solar_midnight", ActionTrigger::SolarMidnight),
+        ]
+        .into_iter()
+        .flat_map(|(field, trigger)| {
+            self.get(trigger)
+                .iter()
+                .map(move |command| (field.to_string(), command.command()))
+        })
+        .chain(self.named.iter().flat_map(|(name, commands)| {
+            commands
+                .as_slice()
+                .iter()
+                .map(move |command| (format!("named.{name}"), command.command()))
+        }))
+        .chain(
+            self.on_action_failure
+                .as_deref()
+                .map(|command| ("on_action_failure".to_string(), command)),
+        )
+        .collect()
+    }
+
+    /// Command run when an action exits unsuccessfully, requires `wait_for_action`
+    pub fn on_action_failure(&self) -> Option<&str> {
+        self.on_action_failure.as_deref()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::NaiveDate;
+    use figment::{
+        Metadata, Profile, Provider,
+        providers::{Format, Toml},
+        value::{Dict, Map},
+    };
+
+    use crate::scheduler::ActionTrigger;
+
+    use super::{
+        ActionCommand, BlackoutDate, Configuration, LocationSource, ManualConfig, Origin, Twilight,
+        expand_path,
+    };
+
+    /// Stands in for the process environment, which the tests running alongside share
+    struct FakeEnv(&'static str);
+
+    impl Provider for FakeEnv {
+        fn metadata(&self) -> Metadata {
+            Metadata::named("environment variable(s)")
         }
+
+        fn data(&self) -> Result<Map<Profile, Dict>, figment::Error> {
+            Toml::string(self.0).data()
+        }
+    }
+
+    #[test]
+    fn default_document_parses() {
+        let doc = Configuration::default_document().unwrap().to_string();
+
+        let config = Configuration::parse(&doc);
+
+        assert!(config.enabled);
+        assert!(config.automatic.is_none());
+        assert!(config.manual.unwrap().time_stamps.is_empty());
+    }
+
+    #[test]
+    fn default_document_parses_uncommented() {
+        let doc = Configuration::default_document().unwrap().to_string();
+        let uncommented = doc
+            .lines()
+            .map(|l| match l.strip_prefix('#') {
+                Some(rest) if !rest.is_empty() && !rest.starts_with(' ') => rest,
+                _ => l,
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let config = Configuration::parse(&uncommented);
+
+        let automatic = config.automatic.unwrap();
+        assert_eq!(automatic.latitude, Some(52.52));
+        assert_eq!(automatic.longitude, Some(13.405));
+        assert_eq!(automatic.location.as_deref(), Some("Berlin"));
+        assert_eq!(automatic.source, LocationSource::Geoclue);
+        assert_eq!(automatic.active_profile.as_deref(), Some("cabin"));
+        assert_eq!(automatic.profiles["cabin"].latitude, Some(61.1));
+        assert!(automatic.event_cache.is_some());
+        assert_eq!(automatic.name.as_deref(), Some("Berlin"));
+        assert_eq!(automatic.twilight, Twilight::Nautical);
+        assert_eq!(automatic.offsets.sunset, -15);
+        assert!(automatic.fallback.sunset.is_some());
+        let manual = config.manual.unwrap();
+        assert_eq!(manual.timezone, Some(chrono_tz::Europe::Berlin));
+        let time_stamps = manual.time_stamps;
+        assert_eq!(time_stamps.len(), 4);
+        assert_eq!(time_stamps[3].days.len(), 5);
+        assert!(config.metrics_file.is_some());
+        assert!(config.audit_log.is_some());
+        assert!(config.reload_validator.is_some());
+        assert!(config.actions.on_dawn.is_some());
+        assert!(config.actions.on_sunrise.is_some());
+        assert!(config.actions.on_sunset.is_some());
+        assert!(config.actions.on_dusk.is_some());
+        assert!(config.actions.on_action_failure.is_some());
+        assert!(!config.actions.named("night_mode").is_empty());
+    }
+
+    #[test]
+    fn expand_path_expands_home() {
+        let home = std::env::var("HOME").unwrap();
+
+        let expanded = expand_path("log_file", "~/hyprsunrisewatcher.log").unwrap();
+
+        assert_eq!(expanded, format!("{home}/hyprsunrisewatcher.log"));
+    }
+
+    #[test]
+    fn expand_path_reports_field() {
+        let err = expand_path("log_file", "$HSW_SURELY_UNSET_VARIABLE/log").unwrap_err();
+
+        assert!(err.to_string().contains("log_file"));
+    }
+
+    #[test]
+    fn actions_without_event_commands_are_empty() {
+        assert!(Configuration::default().actions.is_empty());
+        assert!(
+            Configuration::parse("[actions]\non_action_failure = \"notify-send failed\"")
+                .actions
+                .is_empty()
+        );
+        assert!(
+            !Configuration::parse("[actions]\non_dusk = \"true\"")
+                .actions
+                .is_empty()
+        );
+        assert!(
+            !Configuration::parse("[actions.named]\nnight_mode = \"true\"")
+                .actions
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn validate_rejects_empty_shell() {
+        let config = Configuration::parse("shell = \" \"");
+
+        assert!(matches!(
+            config.validate(),
+            Err(crate::error::Error::InvalidConfiguration(_))
+        ));
+    }
+
+    fn validate_schedule(toml: &str) -> crate::error::Result<Configuration> {
+        let config = Configuration::parse(toml);
+        config.validate().map(|()| config)
+    }
+
+    #[test]
+    fn schedule_is_manual_or_automatic() {
+        let manual = "[[manual.time_stamps]]\ntrigger_time = \"22:00:00\"\naction = \"Dusk\"\n";
+        let automatic = "[automatic]\nlatitude = 52.5\nlongitude = 13.4\n";
+
+        let config = validate_schedule(manual).unwrap();
+        assert_eq!(config.manual.unwrap().time_stamps.len(), 1);
+        assert!(config.automatic.is_none());
+
+        let config = validate_schedule(automatic).unwrap();
+        assert!(config.automatic.is_some());
+
+        let err = validate_schedule(&format!("{manual}{automatic}")).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid configuration: both [manual] time stamps and [automatic] are set, remove one of them"
+        );
+
+        let neither = Configuration {
+            manual: None,
+            automatic: None,
+            ..Configuration::default()
+        };
+        assert_eq!(
+            neither.validate().unwrap_err().to_string(),
+            "Invalid configuration: neither [manual] nor [automatic] is set"
+        );
+    }
+
+    #[test]
+    fn validate_rejects_undefined_names() {
+        let config = Configuration::parse(concat!(
+            "[[manual.time_stamps]]\n",
+            "trigger_time = \"22:00:00\"\n",
+            "action = \"Dusk\"\n",
+            "name = \"night_mode\"\n",
+        ));
+
+        let result = config.validate();
+
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Invalid action: night_mode, not defined in [actions.named]"
+        );
+    }
+
+    #[test]
+    fn sources_separate_file_fields_from_defaults() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(
+            &path,
+            "hot_reload = true\n[actions]\non_sunset = \"true\"\n",
+        )
+        .unwrap();
+
+        let sources = Configuration::sources(path.to_str().unwrap()).unwrap();
+
+        assert!(sources.found);
+        assert_eq!(sources.path, path.to_str().unwrap());
+        assert_eq!(
+            sources.fields_from(Origin::File),
+            ["actions.on_sunset", "hot_reload"]
+        );
+        assert_eq!(sources.fields.get("enabled"), Some(&Origin::Default));
+    }
+
+    #[test]
+    fn environment_overrides_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        let path = path.to_str().unwrap();
+        std::fs::write(path, "drain_timeout_secs = 60\n").unwrap();
+        let env = || FakeEnv("drain_timeout_secs = 5");
+
+        let config = Configuration::load_with_env(path, env());
+        let sources = Configuration::sources_with_env(path, env());
+
+        assert_eq!(config.unwrap().drain_timeout_secs, 5);
+        assert_eq!(
+            sources.unwrap().fields.get("drain_timeout_secs"),
+            Some(&Origin::Environment)
+        );
+    }
+
+    #[test]
+    fn manual_timezone_is_an_iana_name() {
+        let berlin: ManualConfig = toml_edit::de::from_str("timezone = \"Europe/Berlin\"").unwrap();
+        let unknown = toml_edit::de::from_str::<ManualConfig>("timezone = \"Mars/Olympus\"");
+
+        assert_eq!(berlin.timezone, Some(chrono_tz::Europe::Berlin));
+        assert!(unknown.is_err());
+    }
+
+    #[test]
+    fn directory_is_not_a_config_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path().to_str().unwrap();
+
+        let err = Configuration::load(dir).unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            format!("Invalid path for config: {dir} is not a file")
+        );
+        assert!(Configuration::load_existing(dir).is_err());
+    }
+
+    #[test]
+    fn load_existing_requires_file() {
+        let err = Configuration::load_existing("/nonexistent/hyprsunrisewatcher.toml").unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "Config file /nonexistent/hyprsunrisewatcher.toml not found"
+        );
+    }
+
+    #[test]
+    fn sources_without_file_are_all_defaults() {
+        let sources = Configuration::sources("/nonexistent/hyprsunrisewatcher.toml").unwrap();
+
+        assert!(!sources.found);
+        assert_eq!(sources.path, "/nonexistent/hyprsunrisewatcher.toml");
+        assert!(sources.fields_from(Origin::File).is_empty());
+    }
+
+    #[test]
+    fn validate_rejects_name_with_command() {
+        let config = Configuration::parse(concat!(
+            "[actions.named]\n",
+            "night_mode = \"true\"\n",
+            "[[manual.time_stamps]]\n",
+            "trigger_time = \"22:00:00\"\n",
+            "action = \"Dusk\"\n",
+            "name = \"night_mode\"\n",
+            "command = \"true\"\n",
+        ));
+
+        let result = config.validate();
+
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Invalid action: night_mode, a time stamp can't have both a name and a command"
+        );
+    }
+
+    #[test]
+    fn validate_rejects_out_of_range_coordinates() {
+        let config = Configuration::parse("[automatic]\nlatitude = 200.0\nlongitude = 11.0");
+
+        let result = config.validate();
+
+        assert!(matches!(
+            result,
+            Err(crate::error::Error::InvalidCoordinates(lat, long)) if lat == 200.0 && long == 11.0
+        ));
+    }
+
+    #[test]
+    fn blackout_date_parses_both_forms() {
+        let once: BlackoutDate = "2025-12-24".parse().unwrap();
+        let yearly: BlackoutDate = "02-29".parse().unwrap();
+
+        assert_eq!(
+            once,
+            BlackoutDate::Once(NaiveDate::from_ymd_opt(2025, 12, 24).unwrap())
+        );
+        assert_eq!(yearly, BlackoutDate::Yearly { month: 2, day: 29 });
+        assert_eq!(once.to_string(), "2025-12-24");
+        assert_eq!(yearly.to_string(), "02-29");
+        assert!("13-01".parse::<BlackoutDate>().is_err());
+        assert!("christmas".parse::<BlackoutDate>().is_err());
+    }
+
+    #[test]
+    fn blackout_dates_match() {
+        let config = Configuration::parse(r#"blackout_dates = ["2025-12-24", "01-01"]"#);
+
+        assert!(config.is_blackout(NaiveDate::from_ymd_opt(2025, 12, 24).unwrap()));
+        assert!(!config.is_blackout(NaiveDate::from_ymd_opt(2026, 12, 24).unwrap()));
+        assert!(config.is_blackout(NaiveDate::from_ymd_opt(2031, 1, 1).unwrap()));
+    }
+
+    #[test]
+    fn profile_switches_coordinates() {
+        let mut config = Configuration::parse(concat!(
+            "[automatic]\n",
+            "location = \"Berlin\"\n",
+            "[automatic.profiles.cabin]\n",
+            "name = \"Summer cabin\"\n",
+            "latitude = 61.1\n",
+            "longitude = 10.5\n",
+        ));
+        let home = config.automatic.as_ref().unwrap().coordinates().unwrap();
+
+        config.set_profile(Some("cabin".to_string())).unwrap();
+        let auto = config.automatic.as_ref().unwrap();
+        assert_eq!(auto.coordinates().unwrap(), (61.1, 10.5));
+        assert_eq!(config.location_name(), Some("Summer cabin"));
+
+        config.set_profile(None).unwrap();
+        assert_eq!(
+            config.automatic.as_ref().unwrap().coordinates().unwrap(),
+            home
+        );
+        assert_eq!(config.location_name(), Some("Berlin"));
+    }
+
+    #[test]
+    fn unknown_profile_is_rejected() {
+        let mut config = Configuration::parse(concat!(
+            "[automatic]\n",
+            "latitude = 52.5\n",
+            "longitude = 13.4\n",
+            "[automatic.profiles.cabin]\n",
+            "latitude = 61.1\n",
+            "longitude = 10.5\n",
+        ));
+
+        let err = config.set_profile(Some("beach".to_string())).unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "Invalid configuration: unknown profile beach, configured are: cabin"
+        );
+        assert_eq!(config.automatic.unwrap().active_profile, None);
+    }
+
+    #[test]
+    fn is_dark_follows_boundaries() {
+        let sun_up = Configuration::parse("");
+        let civil = Configuration::parse("day_boundary = \"dawn\"\nnight_boundary = \"dusk\"");
+
+        assert!(sun_up.is_dark(ActionTrigger::Dawn));
+        assert!(!sun_up.is_dark(ActionTrigger::Sunrise));
+        assert!(sun_up.is_dark(ActionTrigger::Sunset));
+        assert!(sun_up.is_dark(ActionTrigger::Dusk));
+        assert!(!civil.is_dark(ActionTrigger::Dawn));
+        assert!(!civil.is_dark(ActionTrigger::Sunrise));
+        assert!(!civil.is_dark(ActionTrigger::Sunset));
+        assert!(civil.is_dark(ActionTrigger::Dusk));
+    }
+
+    #[test]
+    fn actions_accept_tables() {
+        let config = Configuration::parse(concat!(
+            "[actions]\n",
+            "on_sunset = \"true\"\n",
+            "on_dusk = { command = \"sleep 60\", detach = true }\n",
+        ));
+
+        assert_eq!(
+            config.actions.get(ActionTrigger::Dusk),
+            [ActionCommand::Detailed {
+                command: "sleep 60".to_string(),
+                detach: true
+            }]
+        );
+        assert!(config.actions.get(ActionTrigger::Dusk)[0].detach());
+        assert!(!config.actions.get(ActionTrigger::Sunset)[0].detach());
+    }
+
+    #[test]
+    fn actions_accept_lists() {
+        let config = Configuration::parse(concat!(
+            "[actions]\n",
+            "on_sunset = [\"first\", { command = \"second\", detach = true }]\n",
+        ));
+
+        let commands = config.actions.get(ActionTrigger::Sunset);
+
+        assert_eq!(
+            commands.iter().map(|c| c.command()).collect::<Vec<_>>(),
+            ["first", "second"]
+        );
+        assert!(commands[1].detach());
+        assert!(config.actions.get(ActionTrigger::Sunrise).is_empty());
+        assert_eq!(
+            config.actions.describe(ActionTrigger::Sunset).as_deref(),
+            Some("first; second")
+        );
     }
 }
@@ -0,0 +1,134 @@
+use std::{
+    io::{BufRead, IsTerminal, Write},
+    path::Path,
+};
+
+use crate::error::Error;
+
+/// What to do when nobody can be asked, i.e. stdin is not a terminal and `--yes` wasn't given
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Unattended {
+    /// The operation can safely be repeated, e.g. stopping the daemon
+    Proceed,
+    /// The operation loses data, e.g. overwriting a file
+    Refuse,
+}
+
+/// Asks `question` on the terminal unless `yes` is set.
+///
+/// Fails with [`Error::NotConfirmed`] if the answer isn't yes, or if there is no terminal and
+/// `unattended` is [`Unattended::Refuse`].
+pub fn confirm(question: &str, yes: bool, unattended: Unattended) -> crate::error::Result<()> {
+    let stdin = std::io::stdin();
+    let interactive = stdin.is_terminal();
+    ask(
+        question,
+        yes,
+        unattended,
+        interactive.then(|| stdin.lock()),
+        std::io::stderr(),
+    )
+}
+
+/// Asks before replacing `path` if it already exists
+pub fn overwrite(path: &str, yes: bool) -> crate::error::Result<()> {
+    if !Path::new(path).exists() {
+        return Ok(());
+    }
+    confirm(&format!("Overwrite {path}?"), yes, Unattended::Refuse)
+}
+
+fn ask(
+    question: &str,
+    yes: bool,
+    unattended: Unattended,
+    terminal: Option<impl BufRead>,
+    mut prompt: impl Write,
+) -> crate::error::Result<()> {
+    if yes {
+        return Ok(());
+    }
+    let Some(mut terminal) = terminal else {
+        return match unattended {
+            Unattended::Proceed => Ok(()),
+            Unattended::Refuse => Err(Error::NotConfirmed(format!(
+                "{question} (pass --yes to confirm)"
+            ))),
+        };
+    };
+    write!(prompt, "{question} [y/N] ")?;
+    prompt.flush()?;
+    let mut answer = String::new();
+    terminal.read_line(&mut answer)?;
+    match answer.trim().to_ascii_lowercase().as_str() {
+        "y" | "yes" => Ok(()),
+        _ => Err(Error::NotConfirmed(question.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{Cursor, empty};
+
+    use super::{Unattended, ask};
+
+    #[test]
+    fn yes_skips_the_question() {
+        let mut prompt = vec![];
+
+        ask(
+            "Overwrite?",
+            true,
+            Unattended::Refuse,
+            Some(empty()),
+            &mut prompt,
+        )
+        .unwrap();
+
+        assert!(prompt.is_empty());
+    }
+
+    #[test]
+    fn unattended_follows_policy() {
+        let no_terminal: Option<Cursor<&[u8]>> = None;
+
+        assert!(
+            ask(
+                "Stop?",
+                false,
+                Unattended::Proceed,
+                no_terminal.clone(),
+                vec![]
+            )
+            .is_ok()
+        );
+        let err = ask("Overwrite?", false, Unattended::Refuse, no_terminal, vec![]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Aborted: Overwrite? (pass --yes to confirm)"
+        );
+    }
+
+    #[test]
+    fn only_yes_confirms() {
+        for (answer, confirmed) in [
+            ("y\n", true),
+            ("YES\n", true),
+            ("n\n", false),
+            ("\n", false),
+        ] {
+            let mut prompt = vec![];
+
+            let result = ask(
+                "Overwrite?",
+                false,
+                Unattended::Proceed,
+                Some(Cursor::new(answer.as_bytes())),
+                &mut prompt,
+            );
+
+            assert_eq!(result.is_ok(), confirmed, "answer {answer:?}");
+            assert_eq!(prompt, b"Overwrite? [y/N] ");
+        }
+    }
+}
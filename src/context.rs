@@ -1,45 +1,111 @@
-use crate::{Args, Commands, daemon::Daemon, info::InfoGatherer, state::AppState};
-use chrono::Utc;
+use crate::{
+    Args, Commands,
+    bench::Benchmark,
+    confirm::{self, Unattended},
+    daemon::Daemon,
+    date,
+    doctor::Doctor,
+    dump_schedule::ScheduleDump,
+    info::{Format, InfoGatherer},
+    lint::Linter,
+    precompute::Precomputer,
+    state::AppState,
+    test_action::ActionTester,
+    upcoming::Upcoming,
+    validate::Validator,
+};
+use std::{process::ExitCode, sync::Arc};
+
+use chrono::{Local, NaiveDate};
 
 use crate::{
+    actions::Action,
     config::Configuration,
     scheduler::{EventSource, TriggerSource},
 };
 
 pub struct Context {
-    pub config: Configuration,
+    /// Shared with the trigger thread of the daemon
+    pub config: Arc<Configuration>,
     pub config_path: String,
 }
 
 impl Context {
     pub fn create_from_config(config: Configuration, config_path: String) -> Self {
         Self {
-            config,
+            config: Arc::new(config),
             config_path,
         }
     }
 
-    pub fn run(self, args: Args) -> Result<(), Box<dyn std::error::Error + 'static>> {
+    pub fn run(self, args: Args) -> crate::error::Result<ExitCode> {
         let state = self.create_execution_state(args)?;
         state.run(self)
     }
 
-    fn gather_info(&self) -> crate::error::Result<AppState> {
+    fn gather_info(
+        &self,
+        date: Option<NaiveDate>,
+        format: Format,
+    ) -> crate::error::Result<AppState> {
         let ts = TriggerSource::from_config(&self.config)?;
-        let next_event_at = ts.next_event_at(Utc::now());
-        Ok(AppState::Info(InfoGatherer::new(next_event_at)))
+        let at = date::reference_time(date);
+        Ok(AppState::Info(InfoGatherer::new(
+            ts.current_event_at(at),
+            ts.next_event_at(at),
+            format,
+            date.is_none(),
+        )))
     }
     fn create_execution_state(&self, args: Args) -> crate::error::Result<AppState> {
         match args.command {
             Some(c) => match c {
-                Commands::Start => self.create_daemon(),
-                Commands::PrintDefaultConfig => self.create_default_config(),
+                Commands::Start { once: false } => self.create_daemon(),
+                Commands::Start { once: true } => Ok(AppState::Once),
+                Commands::Stop => {
+                    confirm::confirm("Stop the running daemon?", args.yes, Unattended::Proceed)?;
+                    Ok(AppState::Send(Action::Stop))
+                }
+                Commands::Enable => Ok(AppState::Send(Action::SetEnabled(true))),
+                Commands::Disable => Ok(AppState::Send(Action::SetEnabled(false))),
+                Commands::Toggle => Ok(AppState::Send(Action::Toggle)),
+                Commands::Info => self.gather_info(args.date, args.format),
+                Commands::PrintDefaultConfig { out } => self.create_default_config(out, args.yes),
+                Commands::Init { force } => Ok(AppState::Init { force }),
+                Commands::Doctor => Ok(AppState::Doctor(Doctor)),
+                Commands::Validate { strict } => Ok(AppState::Validate(Validator::new(strict))),
+                Commands::Lint { strict } => Ok(AppState::Lint(Linter::new(strict))),
+                Commands::Precompute { year, out } => {
+                    Ok(AppState::Precompute(Precomputer::new(year, out, args.yes)))
+                }
+                Commands::Profile { name } => Ok(AppState::Send(Action::SetProfile(name))),
+                Commands::Reload { check: false } => Ok(AppState::Send(Action::ReloadConfig)),
+                Commands::Reload { check: true } => Ok(AppState::Send(Action::CheckConfig)),
+                Commands::IsRunning => Ok(AppState::IsRunning),
+                Commands::Status => Ok(AppState::Status),
+                Commands::IsDark => Ok(AppState::IsDark),
+                Commands::Upcoming { count, json, date } => Ok(AppState::Upcoming(Upcoming::new(
+                    count,
+                    json || args.format == Format::Json,
+                    date::reference_time(date),
+                ))),
+                Commands::DumpSchedule { date } => Ok(AppState::DumpSchedule(ScheduleDump::new(
+                    date.unwrap_or_else(|| Local::now().date_naive()),
+                ))),
+                Commands::TestAction { trigger, dry_run } => {
+                    Ok(AppState::TestAction(ActionTester::new(trigger, dry_run)))
+                }
+                Commands::Bench { iterations } => Ok(AppState::Bench(Benchmark::new(iterations))),
             },
-            None => self.gather_info(),
+            None => self.gather_info(args.date, args.format),
         }
     }
-    fn create_default_config(&self) -> crate::error::Result<AppState> {
-        Ok(AppState::DefaultConfig)
+    fn create_default_config(
+        &self,
+        out: Option<String>,
+        yes: bool,
+    ) -> crate::error::Result<AppState> {
+        Ok(AppState::DefaultConfig { out, yes })
     }
 
     fn create_daemon(&self) -> crate::error::Result<AppState> {
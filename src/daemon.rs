@@ -1,206 +1,1620 @@
 use std::{
+    ffi::OsStr,
     io::{self, BufReader},
-    path::PathBuf,
-    sync::mpsc::{Receiver, Sender, channel},
+    path::{Path, PathBuf},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+        mpsc::{Receiver, RecvTimeoutError, Sender, channel},
+    },
     thread::{JoinHandle, sleep},
-    time::Duration,
+    time::{Duration, Instant},
 };
 
-use crate::{context::Context, scheduler::EventCache};
-use chrono::{TimeDelta, Utc};
+use crate::{
+    audit::{self, Outcome, TriggerRecord},
+    client::{self, DaemonStatus},
+    context::Context,
+    date, desktop, geoclue,
+    info::DaemonInfo,
+    lint::lint_actions,
+    logging,
+    metrics::Metrics,
+    outputs,
+    pid_file::PidFile,
+    runner,
+    scheduler::{ActionTrigger, EventCache, PendingEvent},
+    systemd,
+};
+use chrono::{DateTime, Local, NaiveTime, TimeDelta, TimeZone, Utc};
 use interprocess::local_socket::{
-    GenericNamespaced, Listener, ListenerOptions, Stream, ToNsName, traits::ListenerExt,
+    Listener, ListenerOptions, Name, Stream,
+    traits::{ListenerExt, Stream as _},
 };
-use notify::{INotifyWatcher, RecommendedWatcher, Watcher, recommended_watcher};
+use notify::{RecommendedWatcher, Watcher, recommended_watcher};
 
 use crate::{
-    actions::Action,
-    config::{Configuration, SOCKET_NAME},
+    actions::{Action, Request, Response},
+    config::{self, Configuration, TriggerSettings},
+    runner::RunningActions,
     scheduler::{EventSource, TriggerSource},
 };
 
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_FIRES_PER_SECOND: u32 = 5;
+const RUNAWAY_BACKOFF: Duration = Duration::from_secs(60);
+const MAX_SLEEP: Duration = Duration::from_secs(60 * 60);
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+/// How far the wall clock may drift from the monotonic clock between two ticks before it counts
+/// as stepped, e.g. by NTP or by a suspend the monotonic clock doesn't count
+const CLOCK_JUMP: TimeDelta = TimeDelta::minutes(1);
+
+/// Notices the wall clock moving other than by the time that passed between two ticks
+struct ClockWatch {
+    wall: DateTime<Utc>,
+    monotonic: Instant,
+}
+
+impl ClockWatch {
+    fn new(wall: DateTime<Utc>, monotonic: Instant) -> Self {
+        Self { wall, monotonic }
+    }
+
+    /// Records a tick, returns how far the wall clock jumped since the last one if it did
+    fn tick(&mut self, wall: DateTime<Utc>, monotonic: Instant) -> Option<TimeDelta> {
+        let elapsed = TimeDelta::from_std(monotonic - self.monotonic).unwrap_or(TimeDelta::MAX);
+        let jump = (wall - self.wall) - elapsed;
+        *self = Self::new(wall, monotonic);
+        (jump.abs() > CLOCK_JUMP).then_some(jump)
+    }
+}
+
+/// Counts fired triggers per second, a real schedule never fires more than a handful at once
+struct FireGuard {
+    window_start: Instant,
+    fires: u32,
+}
+
+impl FireGuard {
+    fn new(now: Instant) -> Self {
+        Self {
+            window_start: now,
+            fires: 0,
+        }
+    }
+
+    /// Records a fire, returns true once the rate exceeds `MAX_FIRES_PER_SECOND`
+    fn record(&mut self, now: Instant) -> bool {
+        if now.duration_since(self.window_start) >= Duration::from_secs(1) {
+            self.window_start = now;
+            self.fires = 0;
+        }
+        self.fires += 1;
+        self.fires > MAX_FIRES_PER_SECOND
+    }
+}
+
 pub struct Daemon {
-    pub watcher: Option<INotifyWatcher>,
-    pub sender: Sender<Action>,
-    pub receiver: Receiver<Action>,
-    pub config_sender: Sender<Configuration>,
+    pub watcher: Option<RecommendedWatcher>,
+    pub sender: Sender<Request>,
+    pub receiver: Receiver<Request>,
+    pub config_sender: Sender<Arc<Configuration>>,
+    pub metrics: Metrics,
+    pub running: RunningActions,
+    /// Removed when the daemon is dropped
+    pub pid_file: Option<PidFile>,
+    /// Exits once `config_sender` is dropped
+    pub trigger_thread: Option<JoinHandle<()>>,
+    pub listener: Option<ListenerThread>,
 }
 
 impl Daemon {
+    /// Restarts only what is affected by the change from `old` to `config`, so editing an
+    /// unrelated setting doesn't reset the schedule
     fn recreate(
         mut self,
-        config: &mut Configuration,
+        old: &Configuration,
+        config: &Arc<Configuration>,
         config_path: PathBuf,
     ) -> crate::error::Result<Self> {
-        if !config.hot_reload {
-            self.watcher = None;
-        } else {
-            self.watcher = Some(start_hot_reload(config_path, self.sender.clone())?);
+        if old.hot_reload != config.hot_reload
+            || old.reload_debounce_ms != config.reload_debounce_ms
+        {
+            self.watcher = if config.hot_reload {
+                Some(start_hot_reload(
+                    config_path,
+                    self.sender.clone(),
+                    config.reload_debounce(),
+                )?)
+            } else {
+                None
+            };
+        }
+        if old.trigger_thread_differs(config)
+            && self.config_sender.send(Arc::clone(config)).is_err()
+        {
+            log::warn!("Trigger thread is gone, the new configuration won't be scheduled");
         }
-        self.config_sender.send(config.clone())?;
 
         Ok(self)
     }
 
     pub fn run(mut self, mut context: Context) -> crate::error::Result<()> {
-        while let Ok(c) = self.receiver.recv() {
-            if c == Action::Stop {
+        self.export_metrics(&context.config);
+        while let Ok(Request { action, reply }) = self.receiver.recv() {
+            if action == Action::Stop {
+                notify_systemd("STOPPING=1");
+                self.drain(&context.config);
+                self.shutdown();
                 break;
             }
-            self = handle_command(c, &mut context.config, self, &context.config_path)?;
+            let response = match action {
+                Action::CheckConfig => check_config(&context.config_path),
+                Action::QueryInfo => query_info(&context.config, Utc::now()),
+                Action::SetEnabled(enabled) => {
+                    Arc::make_mut(&mut context.config).enabled = enabled;
+                    Response::Enabled(enabled)
+                }
+                Action::SetProfile(profile) => self.set_profile(&mut context.config, profile),
+                action => {
+                    self = handle_command(action, &mut context.config, self, &context.config_path)?;
+                    Response::Done
+                }
+            };
+            if let Some(reply) = reply {
+                // the client may have given up waiting already
+                let _ = reply.send(response);
+            }
+            self.export_metrics(&context.config);
         }
         Ok(())
     }
 
+    /// Reschedules for the location `profile`, keeps the current one if there is no such profile
+    fn set_profile(&self, config: &mut Arc<Configuration>, profile: Option<String>) -> Response {
+        let mut switched = Configuration::clone(config);
+        if let Err(err) = switched.set_profile(profile) {
+            return Response::Error(err.to_string());
+        }
+        log::info!(
+            "Switched location to {}",
+            switched
+                .location_name()
+                .unwrap_or("the configured coordinates")
+        );
+        *config = Arc::new(switched);
+        if self.config_sender.send(Arc::clone(config)).is_err() {
+            log::warn!("Trigger thread is gone, the new location won't be scheduled");
+        }
+        Response::Done
+    }
+
+    /// Stops the listener and the trigger thread, waiting up to `SHUTDOWN_TIMEOUT` for each
+    fn shutdown(&mut self) {
+        if let Some(listener) = self.listener.take()
+            && !listener.stop(SHUTDOWN_TIMEOUT)
+        {
+            log::warn!(
+                "Socket listener still running after {}s, exiting anyway",
+                SHUTDOWN_TIMEOUT.as_secs()
+            );
+        }
+        // replacing the sender disconnects the trigger thread's receiver
+        self.config_sender = channel().0;
+        if let Some(thread) = self.trigger_thread.take()
+            && !join_timeout(thread, SHUTDOWN_TIMEOUT)
+        {
+            log::warn!(
+                "Trigger thread still running after {}s, exiting anyway",
+                SHUTDOWN_TIMEOUT.as_secs()
+            );
+        }
+    }
+
+    /// Waits for running actions if `drain_actions` is set
+    fn drain(&self, config: &Configuration) {
+        if config.drain_actions
+            && !self
+                .running
+                .wait_idle(Duration::from_secs(config.drain_timeout_secs))
+        {
+            log::warn!(
+                "Actions still running after {}s, continuing anyway",
+                config.drain_timeout_secs
+            );
+        }
+    }
+
+    fn export_metrics(&self, config: &Configuration) {
+        if let Some(path) = &config.metrics_file
+            && let Err(err) = self.metrics.write(path, config)
+        {
+            log::warn!("Failed to write metrics to {path}: {err}");
+        }
+    }
+
     pub fn create(context: &Context) -> crate::error::Result<Self> {
+        context.config.validate()?;
         let (sender, receiver) = channel();
         let (sender_config, receiver_config) = channel();
-        let name = SOCKET_NAME.to_ns_name::<GenericNamespaced>()?;
-        let opts = ListenerOptions::new().name(name.clone());
+        let listener = bind()?;
+        let pid_file = PidFile::create(&context.config.pid_file)
+            .inspect_err(|err| {
+                log::warn!(
+                    "Failed to write PID file {}: {err}",
+                    context.config.pid_file
+                )
+            })
+            .ok();
         setup_sig_handler(sender.clone())?;
-        if let Ok(listener) = opts.create_sync() {
-            let sc = sender.clone();
-            std::thread::spawn(move || start_translate_events(sc, listener));
-            let _trigger_thread = setup_trigger(sender.clone(), receiver_config)?;
-            sender_config.send(context.config.clone())?;
-            let mut watcher = None;
-            if context.config.hot_reload {
-                watcher = Some(start_hot_reload(
-                    context.config_path.clone().into(),
-                    sender.clone(),
-                )?);
+        if context.config.automatic.is_some() && context.config.actions.is_empty() {
+            log::warn!("No actions configured, events will fire without running anything");
+        }
+        for finding in lint_actions(&context.config.actions) {
+            log::warn!("Suspicious action {finding}");
+        }
+        let listener = ListenerThread::spawn(sender.clone(), listener, client::socket_name()?);
+        let trigger_thread = setup_trigger(sender.clone(), receiver_config)?;
+        sender_config.send(Arc::clone(&context.config))?;
+        if context.config.run_on_start
+            && let Some(action) = current_trigger(&context.config, Utc::now())?
+        {
+            sender.send(action.into())?;
+        }
+        let mut watcher = None;
+        if context.config.hot_reload {
+            watcher = Some(start_hot_reload(
+                context.config_path.clone().into(),
+                sender.clone(),
+                context.config.reload_debounce(),
+            )?);
+        }
+        notify_systemd("READY=1");
+        Ok(Daemon {
+            watcher,
+            sender,
+            receiver,
+            config_sender: sender_config,
+            metrics: Metrics::default(),
+            running: RunningActions::default(),
+            pid_file,
+            trigger_thread: Some(trigger_thread),
+            listener: Some(listener),
+        })
+    }
+}
+
+fn notify_systemd(state: &str) {
+    if let Err(err) = systemd::notify(state) {
+        log::warn!("Failed to notify systemd of {state}: {err}");
+    }
+}
+
+/// The trigger of the period `now` falls into, `None` if it has no action
+fn current_trigger(
+    config: &Configuration,
+    now: DateTime<Utc>,
+) -> crate::error::Result<Option<Action>> {
+    let source = TriggerSource::locate(config.trigger_settings())?;
+    let Some(current) = source.current_event_at(now) else {
+        return Ok(None);
+    };
+    let trigger = current.trigger;
+    let at = current.since.unwrap_or(now);
+    let commands = source.commands_at(trigger, at);
+    Ok((!commands.is_empty()).then(|| Action::Trigger {
+        trigger,
+        at,
+        commands: commands.to_vec(),
+    }))
+}
+
+/// Runs the actions of the current event like `run_on_start` and waits up to `drain_timeout_secs`
+/// for them, without binding the socket or starting the trigger thread
+pub fn run_once(context: Context) -> crate::error::Result<()> {
+    let mut config = context.config;
+    let Some(action) = current_trigger(&config, Utc::now())? else {
+        log::info!("No actions configured for the current event");
+        return Ok(());
+    };
+    let (sender, receiver) = channel();
+    let mut daemon = Daemon {
+        watcher: None,
+        sender,
+        receiver,
+        config_sender: channel().0,
+        metrics: Metrics::default(),
+        running: RunningActions::default(),
+        pid_file: None,
+        trigger_thread: None,
+        listener: None,
+    };
+    daemon = handle_command(action, &mut config, daemon, &context.config_path)?;
+    let timeout = Duration::from_secs(config.drain_timeout_secs);
+    if !daemon.running.wait_idle(timeout) {
+        log::warn!(
+            "Actions still running after {}s, exiting anyway",
+            timeout.as_secs()
+        );
+    }
+    // reported with `wait_for_action`, may start `on_action_failure`
+    while let Ok(Request { action, .. }) = daemon.receiver.try_recv() {
+        daemon = handle_command(action, &mut config, daemon, &context.config_path)?;
+    }
+    daemon.running.wait_idle(timeout);
+    Ok(())
+}
+
+/// Binds the daemon socket, replacing a socket file left behind by a daemon that crashed. Takes
+/// the socket of a systemd socket unit instead if there is one.
+fn bind() -> crate::error::Result<Listener> {
+    #[cfg(unix)]
+    if let Some(fd) = systemd::listen_fd()? {
+        log::info!("Listening on the socket passed by systemd");
+        return Ok(interprocess::os::unix::uds_local_socket::Listener::from(fd).into());
+    }
+    let listen = || -> io::Result<Listener> {
+        ListenerOptions::new()
+            .name(client::socket_name()?)
+            .create_sync()
+    };
+    let err = match listen() {
+        Ok(listener) => return Ok(listener),
+        Err(err) => err,
+    };
+    match client::daemon_status() {
+        DaemonStatus::Running => Err(crate::error::Error::DaemonAlreadyRunning),
+        DaemonStatus::Stale => {
+            log::warn!("Replacing the stale socket of a daemon that didn't shut down cleanly");
+            #[cfg(unix)]
+            match std::fs::remove_file(client::socket_file()) {
+                Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err.into()),
+                _ => {}
             }
-            Ok(Daemon {
-                watcher,
-                sender,
-                receiver,
-                config_sender: sender_config,
-            })
-        } else {
-            Err(crate::error::Error::FailedtoCreateDaemon.into())
+            Ok(listen()?)
         }
+        DaemonStatus::NotRunning => Err(err.into()),
     }
 }
 
 fn start_hot_reload(
     config_path: PathBuf,
-    sender: Sender<Action>,
+    sender: Sender<Request>,
+    window: Duration,
 ) -> crate::error::Result<RecommendedWatcher> {
+    let (modified, events) = channel();
+    let file_name = config_path.file_name().unwrap_or_default().to_owned();
     let mut watcher = recommended_watcher(move |ev: Result<notify::Event, notify::Error>| {
-        if let Ok(e) = ev {
-            if let notify::EventKind::Modify(_) = e.kind {
-                sender
-                    .send(Action::ReloadConfig)
-                    .expect("failed to send hot reload event");
-            }
+        if let Ok(e) = ev
+            && is_config_change(&e, &file_name)
+        {
+            let _ = modified.send(());
         }
     })?;
-    watcher.watch(&config_path, notify::RecursiveMode::NonRecursive)?;
+    // editors that save by renaming a temporary file over the config replace its inode, which
+    // ends a watch on the file itself, while the directory stays the same
+    let dir = match config_path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    watcher.watch(dir, notify::RecursiveMode::NonRecursive)?;
+    // ends when the watcher is dropped together with its end of the channel
+    std::thread::spawn(move || {
+        debounce(&events, window, || {
+            // the daemon may already be shutting down
+            sender.send(Action::ReloadConfig.into()).is_ok()
+        })
+    });
     Ok(watcher)
 }
 
+/// Whether `event` in the config directory changed the file named `file_name`
+fn is_config_change(event: &notify::Event, file_name: &OsStr) -> bool {
+    matches!(
+        event.kind,
+        notify::EventKind::Create(_) | notify::EventKind::Modify(_)
+    ) && event
+        .paths
+        .iter()
+        .any(|path| path.file_name() == Some(file_name))
+}
+
+/// Calls `reload` once for every burst of events less than `window` apart.
+///
+/// Returns once the events stop for good or `reload` reports that nobody is listening.
+fn debounce(events: &Receiver<()>, window: Duration, mut reload: impl FnMut() -> bool) {
+    while events.recv().is_ok() {
+        loop {
+            match events.recv_timeout(window) {
+                Ok(()) => {}
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+        }
+        if !reload() {
+            return;
+        }
+    }
+}
+
+/// Why the trigger thread stopped waiting
+enum Wake {
+    Timeout,
+    Config(Arc<Configuration>),
+    Disconnected,
+}
+
+/// Waits up to `timeout`, returning early when a new configuration arrives
+fn park(receiver: &Receiver<Arc<Configuration>>, timeout: Duration) -> Wake {
+    match receiver.recv_timeout(timeout) {
+        Ok(config) => Wake::Config(config),
+        Err(RecvTimeoutError::Timeout) => Wake::Timeout,
+        Err(RecvTimeoutError::Disconnected) => Wake::Disconnected,
+    }
+}
+
+/// Time until the next local midnight, or the start of the day if the clock skips midnight
+fn until_midnight<Tz: TimeZone>(now: DateTime<Tz>) -> Duration {
+    now.date_naive()
+        .succ_opt()
+        .and_then(|tomorrow| {
+            date::local_instant(tomorrow.and_time(NaiveTime::MIN), &now.timezone())
+        })
+        .and_then(|midnight| (midnight - now).to_std().ok())
+        .unwrap_or(MAX_SLEEP)
+}
+
+/// Time until `next` is due, capped to `MAX_SLEEP` so wall clock changes are noticed eventually
+fn wait_duration(now: DateTime<Utc>, next: Option<&PendingEvent>) -> Duration {
+    match next {
+        // an overdue event is resolved on the next tick, don't spin meanwhile
+        Some(next) => (next.at - now)
+            .to_std()
+            .unwrap_or(Duration::from_secs(1))
+            .min(MAX_SLEEP),
+        None => MAX_SLEEP,
+    }
+}
+
+/// A fresh schedule for `config`, the cache starts empty so no event of a previous location fires
+fn schedule(settings: TriggerSettings) -> crate::error::Result<(TriggerSource, EventCache)> {
+    Ok((TriggerSource::locate(settings)?, EventCache::new()))
+}
+
+/// The last event with commands after `since` and up to `now`, the one whose action sets the
+/// state after resuming from a suspend that lasted from `since` to `now`
+fn last_missed(
+    scheduler: &impl EventSource,
+    since: DateTime<Utc>,
+    now: DateTime<Utc>,
+) -> Option<(ActionTrigger, DateTime<Utc>)> {
+    scheduler
+        .events_between(since, now + TimeDelta::seconds(1))
+        .into_iter()
+        .rev()
+        .find(|event| event.action.is_some())
+        .map(|event| (event.trigger, event.at))
+}
+
+/// Sends the action of the last event missed between `since` and `now` once `delay` passed,
+/// without holding up the caller meanwhile. Returns the event caught up on.
+fn catch_up_missed(
+    scheduler: &TriggerSource,
+    since: DateTime<Utc>,
+    now: DateTime<Utc>,
+    sender: &Sender<Request>,
+    delay: Duration,
+) -> Option<ActionTrigger> {
+    let (trigger, at) = last_missed(scheduler, since, now)?;
+    let commands = scheduler.commands_at(trigger, at).to_vec();
+    let action = Action::Trigger {
+        trigger,
+        at,
+        commands,
+    };
+    runner::send_delayed(sender.clone(), action, delay);
+    Some(trigger)
+}
+
 fn run_trigger_thread(
-    sender: Sender<Action>,
-    receiver: Receiver<Configuration>,
+    sender: Sender<Request>,
+    receiver: Receiver<Arc<Configuration>>,
 ) -> crate::error::Result<()> {
-    let mut scheduler = None;
-    let mut cache = EventCache::new();
+    let (mut scheduler, mut cache, mut catch_up, mut resume_delay) = match receiver.recv() {
+        Ok(config) => {
+            let settings = config.trigger_settings();
+            let delay = Duration::from_secs(settings.resume_delay_seconds);
+            let catch_up = settings.catch_up_on_resume;
+            let (scheduler, cache) = schedule(settings)?;
+            (scheduler, cache, catch_up, delay)
+        }
+        Err(_) => return Ok(()),
+    };
+    let mut guard = FireGuard::new(Instant::now());
+    let mut clock = ClockWatch::new(Utc::now(), Instant::now());
+    let watchdog = systemd::watchdog_interval();
     loop {
-        match receiver.try_recv() {
-            Ok(config) => scheduler = Some(TriggerSource::from_config(&config)?),
-            Err(try_err) => match try_err {
-                std::sync::mpsc::TryRecvError::Empty => {}
-                std::sync::mpsc::TryRecvError::Disconnected => return Ok(()),
-            },
+        if watchdog.is_some() {
+            notify_systemd("WATCHDOG=1");
         }
-        if let Some(source) = &mut scheduler {
-            let now = Utc::now();
-            if let Some(action) = source.should_trigger(now, &mut cache) {
-                sender.send(Action::Trigger { action })?;
-            } else {
-                sleep(Duration::from_secs(25))
+        let now = Utc::now();
+        let since = clock.wall;
+        if let Some(jump) = clock.tick(now, Instant::now()) {
+            log::info!(
+                "Clock jumped by {}s, e.g. after suspend, rescheduling",
+                jump.num_seconds()
+            );
+            // the pending event mustn't fire late on its own
+            let missed = cache.missed(now);
+            if catch_up && jump > TimeDelta::zero() {
+                // the compositor may not be back yet, the following events mustn't wait for it
+                if let Some(trigger) =
+                    catch_up_missed(&scheduler, since, now, &sender, resume_delay)
+                {
+                    log::info!(
+                        "Catching up on the {} event in {}",
+                        logging::paint(trigger),
+                        logging::duration(resume_delay)
+                    );
+                }
+            } else if let Some((trigger, _)) = missed {
+                log::info!(
+                    "Skipped the {} event, enable catch_up_on_resume to run it",
+                    logging::paint(trigger)
+                );
             }
         }
+        if let Some((trigger, at)) = scheduler.should_trigger(now, &mut cache) {
+            let commands = scheduler.commands_at(trigger, at).to_vec();
+            if sender
+                .send(
+                    Action::Trigger {
+                        trigger,
+                        at,
+                        commands,
+                    }
+                    .into(),
+                )
+                .is_err()
+            {
+                // the daemon stopped
+                return Ok(());
+            }
+            if guard.record(Instant::now()) {
+                log::error!(
+                    "More than {MAX_FIRES_PER_SECOND} triggers within a second, probable scheduling bug. Backing off for {}s",
+                    RUNAWAY_BACKOFF.as_secs()
+                );
+                sleep(RUNAWAY_BACKOFF);
+                guard = FireGuard::new(Instant::now());
+            }
+            continue;
+        }
+        // the day's events are settled by then, and daylight saving time may have changed
+        let mut wait = wait_duration(now, cache.pending()).min(until_midnight(Local::now()));
+        if let Some(interval) = watchdog {
+            wait = wait.min(interval);
+        }
+        log::debug!(
+            "Sleeping {}, next wake at {}",
+            logging::duration(wait),
+            (Local::now() + wait).format("%H:%M:%S")
+        );
+        match park(&receiver, wait) {
+            Wake::Timeout => log::debug!("Woke up after timeout"),
+            Wake::Config(config) => {
+                log::debug!("Woke up for configuration change");
+                let settings = config.trigger_settings();
+                catch_up = settings.catch_up_on_resume;
+                resume_delay = Duration::from_secs(settings.resume_delay_seconds);
+                (scheduler, cache) = schedule(settings)?;
+            }
+            Wake::Disconnected => return Ok(()),
+        }
     }
 }
 
 fn setup_trigger(
-    sender: Sender<Action>,
-    receiver: Receiver<Configuration>,
+    sender: Sender<Request>,
+    receiver: Receiver<Arc<Configuration>>,
 ) -> crate::error::Result<JoinHandle<()>> {
     Ok(std::thread::spawn(move || {
         run_trigger_thread(sender, receiver).expect("Failed to gracefully shutdown thread")
     }))
 }
 
+/// Joins `thread` unless it is still running after `timeout`, returns whether it was joined
+fn join_timeout(thread: JoinHandle<()>, timeout: Duration) -> bool {
+    let deadline = Instant::now() + timeout;
+    while !thread.is_finished() {
+        if Instant::now() >= deadline {
+            return false;
+        }
+        sleep(Duration::from_millis(10));
+    }
+    if thread.join().is_err() {
+        log::error!("Thread panicked before shutdown");
+    }
+    true
+}
+
+/// The thread accepting client connections. Dropping its listener on exit removes the socket.
+pub struct ListenerThread {
+    thread: JoinHandle<()>,
+    stopping: Arc<AtomicBool>,
+    name: Name<'static>,
+}
+
+impl ListenerThread {
+    fn spawn(sender: Sender<Request>, socket: Listener, name: Name<'static>) -> Self {
+        let stopping = Arc::new(AtomicBool::new(false));
+        let flag = Arc::clone(&stopping);
+        Self {
+            thread: std::thread::spawn(move || start_translate_events(sender, socket, &flag)),
+            stopping,
+            name,
+        }
+    }
+
+    /// Stops accepting connections, returns whether the thread exited within `timeout`
+    fn stop(self, timeout: Duration) -> bool {
+        self.stopping.store(true, Ordering::SeqCst);
+        // accept blocks until someone connects
+        let _ = Stream::connect(self.name);
+        join_timeout(self.thread, timeout)
+    }
+}
+
 fn handle_error(conn: io::Result<Stream>) -> Option<Stream> {
     match conn {
         Ok(s) => Some(s),
         Err(err) => {
-            eprintln!("Incoming connection failed: {err}");
+            log::warn!("Incoming connection failed: {err}");
             None
         }
     }
 }
-fn start_translate_events(sender: Sender<Action>, socket: Listener) {
-    for conn in socket.incoming().filter_map(handle_error) {
+fn start_translate_events(sender: Sender<Request>, socket: Listener, stopping: &AtomicBool) {
+    for conn in socket.incoming() {
+        if stopping.load(Ordering::SeqCst) {
+            break;
+        }
+        let Some(conn) = handle_error(conn) else {
+            continue;
+        };
         let mut bufread = BufReader::new(conn);
         let s = sender.clone();
         std::thread::spawn(move || {
-            while let Ok(action) =
-                bincode::decode_from_std_read(&mut bufread, bincode::config::standard())
-            {
-                s.send(action).expect("Failed to send action");
+            while let Ok(action) = bincode::decode_from_std_read::<Action, _, _>(
+                &mut bufread,
+                bincode::config::standard(),
+            ) {
+                // a failed send means the daemon stopped
+                if !action.expects_response() {
+                    if s.send(action.into()).is_err() {
+                        break;
+                    }
+                    continue;
+                }
+                let (reply, response) = channel();
+                let request = Request {
+                    action,
+                    reply: Some(reply),
+                };
+                if s.send(request).is_err() {
+                    break;
+                }
+                let response = response
+                    .recv_timeout(RESPONSE_TIMEOUT)
+                    .unwrap_or_else(|_| Response::Error("no response from daemon".to_string()));
+                if let Err(err) = bincode::encode_into_std_write(
+                    response,
+                    bufread.get_mut(),
+                    bincode::config::standard(),
+                ) {
+                    log::warn!("Failed to send response: {err}");
+                    break;
+                }
             }
         });
     }
 }
-fn setup_sig_handler(sender: Sender<Action>) -> crate::error::Result<()> {
+fn setup_sig_handler(sender: Sender<Request>) -> crate::error::Result<()> {
     ctrlc::set_handler(move || {
-        sender
-            .send(Action::Stop)
-            .expect("Failed to Stop daemon on sig handler")
+        // a second signal during shutdown finds the daemon gone
+        let _ = sender.send(Action::Stop.into());
     })?;
     Ok(())
 }
 
+/// Loads and validates the config file without touching the running configuration
+fn check_config(config_path: &str) -> Response {
+    match Configuration::load(config_path).and_then(|config| config.validate()) {
+        Ok(()) => Response::ConfigValid,
+        Err(err) => Response::ConfigInvalid(err.to_string()),
+    }
+}
+
+/// The current and next event of `config` at `now`, as the trigger thread schedules them
+fn query_info(config: &Configuration, now: DateTime<Utc>) -> Response {
+    match TriggerSource::locate(config.trigger_settings()) {
+        Ok(source) => Response::Info(DaemonInfo {
+            current_event: source.current_event_at(now),
+            next_event: source.next_event_at(now),
+            enabled: config.enabled,
+            now,
+        }),
+        Err(err) => Response::Error(err.to_string()),
+    }
+}
+
+/// Loads the config file, retrying while it can't be opened (editors may truncate or replace it
+/// while saving). Gives up after `retries` attempts and returns `None`, as it does for a file
+/// that doesn't load, so a broken edit can't stop the daemon.
+fn reload_config(config_path: &str, retries: u32, delay: Duration) -> Option<Configuration> {
+    for attempt in 0..=retries {
+        if std::fs::File::open(config_path).is_ok() {
+            return Configuration::load(config_path)
+                .and_then(|config| config.validate().map(|()| config))
+                .inspect_err(|err| {
+                    log::error!(
+                        "Failed to reload {config_path}: {err}, keeping the current configuration"
+                    )
+                })
+                .ok();
+        }
+        if attempt < retries {
+            sleep(delay);
+        }
+    }
+    log::error!(
+        "Config file {config_path} still unreadable after {retries} retries, keeping the current configuration"
+    );
+    None
+}
+
 fn handle_command(
     command: Action,
-    config: &mut Configuration,
+    config: &mut Arc<Configuration>,
     mut daemon: Daemon,
     config_path: &str,
 ) -> crate::error::Result<Daemon> {
     match command {
-        Action::Stop => {
-            unreachable!("this should never happen!")
+        Action::Stop
+        | Action::CheckConfig
+        | Action::QueryInfo
+        | Action::SetEnabled(_)
+        | Action::SetProfile(_) => {
+            unreachable!("handled in Daemon::run")
         }
-        Action::Enable => config.enabled = true,
-        Action::Disable => config.enabled = false,
-        Action::Toggle => config.enabled = !config.enabled,
+        // the trigger thread only holds the configuration while scheduling, so this rarely copies
+        Action::Enable => Arc::make_mut(config).enabled = true,
+        Action::Disable => Arc::make_mut(config).enabled = false,
+        Action::Toggle => Arc::make_mut(config).enabled = !config.enabled,
         Action::ReloadConfig => {
-            if std::fs::OpenOptions::new()
-                .write(false)
-                .read(true)
-                .open(config_path)
-                .is_err()
+            geoclue::forget();
+            let delay = Duration::from_millis(config.reload_retry_delay_ms);
+            if let Some(reloaded) = reload_config(config_path, config.reload_retries, delay) {
+                let timeout = Duration::from_secs(config.reload_validator_timeout_secs);
+                // the validator of the running configuration decides, so a reload can't drop it
+                if let Some(validator) = &config.reload_validator
+                    && let Err(err) =
+                        runner::validate_reload(config, validator, config_path, timeout)
+                {
+                    log::error!("{err}, keeping the current configuration");
+                } else {
+                    daemon.drain(config);
+                    let old = std::mem::replace(config, Arc::new(reloaded));
+                    daemon = daemon.recreate(&old, config, config_path.into())?;
+                }
+            }
+        }
+        Action::Trigger {
+            trigger,
+            at,
+            commands,
+        } => {
+            let today = Local::now().date_naive();
+            let outcome = if config.is_blackout(today) {
+                Outcome::Blackout(today)
+            } else if config.enabled {
+                Outcome::Ran
+            } else {
+                Outcome::Disabled
+            };
+            let record = TriggerRecord {
+                at: Utc::now(),
+                trigger,
+                commands: config::describe(&commands),
+                location: config.location_name(),
+                outcome,
+            };
+            log::info!("{record}");
+            if let Some(path) = &config.audit_log
+                && let Err(err) = audit::append(path, &record, config.audit_log_max_bytes)
             {
-                sleep(Duration::from_millis(100));
-                daemon.sender.send(Action::ReloadConfig)?;
-                return Ok(daemon);
+                log::warn!("Failed to append to audit log {path}: {err}");
+            }
+            if outcome == Outcome::Ran {
+                let next =
+                    TriggerSource::locate(config.trigger_settings())?.next_event_at(Utc::now());
+                let outputs = outputs::outputs(config);
+                for action in &commands {
+                    let detach = action.detach();
+                    let commands = runner::commands(config, action.command(), &outputs, detach);
+                    if commands.is_empty() {
+                        log::warn!(
+                            "Skipping {} action, no outputs to substitute for {{output}}",
+                            logging::paint(trigger)
+                        );
+                    }
+                    for mut command in commands {
+                        runner::event_env(
+                            &mut command,
+                            trigger,
+                            at,
+                            config.enabled,
+                            config.location_name(),
+                            next.as_ref(),
+                        );
+                        if config.log_actions && !detach {
+                            runner::capture_output(&mut command);
+                        }
+                        runner::own_process_group(&mut command);
+                        let child = command.spawn()?;
+                        daemon.metrics.triggers_total += 1;
+                        // a detached action has no exit code to report
+                        let report =
+                            (config.wait_for_action && !detach).then(|| daemon.sender.clone());
+                        runner::reap(
+                            child,
+                            trigger,
+                            daemon.running.clone(),
+                            report,
+                            config.action_timeout(),
+                        );
+                    }
+                }
+                if config.notify_on_trigger {
+                    desktop::notify_trigger(trigger, Local::now(), config.location_name());
+                }
             }
-
-            *config = Configuration::load(config_path)?;
-            daemon = daemon.recreate(config, config_path.into())?;
         }
-        Action::Trigger { action } => {
-            if config.enabled {
-                std::process::Command::new("sh")
-                    .arg("-c")
-                    .arg(action)
-                    .spawn()?;
+        Action::ActionFinished { trigger, exit_code } => {
+            daemon.metrics.last_action_exit_code = exit_code;
+            if exit_code != Some(0) {
+                log::warn!(
+                    "{} action failed with exit code {exit_code:?}",
+                    logging::paint(trigger)
+                );
+                if let Some(hook) = config.actions.on_action_failure() {
+                    let mut command = runner::command(config, hook);
+                    command.env("HSW_TRIGGER", trigger.to_string()).env(
+                        "HSW_EXIT_CODE",
+                        exit_code.map(|c| c.to_string()).unwrap_or_default(),
+                    );
+                    if config.log_actions {
+                        runner::capture_output(&mut command);
+                    }
+                    runner::own_process_group(&mut command);
+                    let child = command.spawn()?;
+                    runner::reap(
+                        child,
+                        trigger,
+                        daemon.running.clone(),
+                        None,
+                        config.action_timeout(),
+                    );
+                }
             }
         }
         Action::Nothing => {}
     };
     Ok(daemon)
 }
+
+#[cfg(test)]
+mod test {
+    use std::{
+        sync::{Arc, mpsc::channel},
+        time::{Duration, Instant},
+    };
+
+    use chrono::{DateTime, Local, NaiveDate, TimeDelta, TimeZone, Utc};
+    use interprocess::local_socket::{
+        GenericNamespaced, ListenerOptions, Stream, ToNsName, traits::Stream as _,
+    };
+    use sunrise::Coordinates;
+
+    use crate::{
+        actions::{Action, Request, Response},
+        config::{ActionCommand, Actions, Configuration},
+        context::Context,
+        metrics::Metrics,
+        runner::RunningActions,
+        scheduler::{
+            ActionTrigger, EventSource, LocationInfo, PendingEvent, Scheduler, TriggerSource,
+        },
+    };
+
+    use super::{
+        ClockWatch, Daemon, FireGuard, ListenerThread, MAX_FIRES_PER_SECOND, MAX_SLEEP,
+        SHUTDOWN_TIMEOUT, Wake, catch_up_missed, current_trigger, debounce, handle_command,
+        join_timeout, last_missed, park, query_info, reload_config, run_once, schedule,
+        setup_trigger, start_hot_reload, until_midnight, wait_duration,
+    };
+
+    /// Reloads from `old` to `new`, returns the configuration handed to the trigger thread
+    fn reload_with(old: &str, new: &str) -> Option<Configuration> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        let path = path.to_str().unwrap();
+        std::fs::write(path, old).unwrap();
+        let mut config = Arc::new(Configuration::load(path).unwrap());
+        std::fs::write(path, new).unwrap();
+        let (sender, receiver) = channel();
+        let (config_sender, config_receiver) = channel();
+        let daemon = Daemon {
+            watcher: None,
+            sender,
+            receiver,
+            config_sender,
+            metrics: Metrics::default(),
+            running: RunningActions::default(),
+            pid_file: None,
+            trigger_thread: None,
+            listener: None,
+        };
+
+        handle_command(Action::ReloadConfig, &mut config, daemon, path).unwrap();
+
+        let sent = config_receiver.try_recv().ok();
+        // the trigger thread gets the daemon's configuration, not a copy of it
+        assert!(sent.as_ref().is_none_or(|sent| Arc::ptr_eq(sent, &config)));
+        sent.map(Arc::unwrap_or_clone)
+    }
+
+    #[test]
+    fn current_trigger_runs_action_of_current_period() {
+        let config = Configuration::parse(concat!(
+            "[[manual.time_stamps]]\n",
+            "trigger_time = \"07:00:00\"\n",
+            "action = \"Sunrise\"\n",
+            "[[manual.time_stamps]]\n",
+            "trigger_time = \"21:00:00\"\n",
+            "action = \"Sunset\"\n",
+            "[actions]\n",
+            "on_sunrise = \"day\"\n",
+        ));
+        let at = |time: &str| {
+            NaiveDate::from_ymd_opt(2025, 7, 13)
+                .unwrap()
+                .and_time(time.parse().unwrap())
+                .and_local_timezone(Local)
+                .unwrap()
+                .with_timezone(&Utc)
+        };
+
+        let midday = current_trigger(&config, at("12:00:00")).unwrap();
+        let night = current_trigger(&config, at("23:00:00")).unwrap();
+
+        assert_eq!(
+            midday,
+            Some(Action::Trigger {
+                trigger: ActionTrigger::Sunrise,
+                at: at("07:00:00"),
+                commands: vec![ActionCommand::Command("day".to_string())]
+            })
+        );
+        assert_eq!(night, None);
+    }
+
+    #[test]
+    fn reload_keeps_schedule_for_unrelated_change() {
+        let location = "[automatic]\nlatitude = 52.5\nlongitude = 13.4\n";
+
+        let rescheduled = reload_with(
+            &format!("reload_retries = 5\n{location}"),
+            &format!("reload_retries = 7\n{location}"),
+        );
+
+        assert!(rescheduled.is_none());
+    }
+
+    #[test]
+    fn log_setting_change_keeps_event_cache() {
+        let location = "[automatic]\nlatitude = 52.5\nlongitude = 13.4\n";
+
+        let rescheduled = reload_with(
+            &format!("log_actions = false\n{location}"),
+            &format!("log_actions = true\n{location}"),
+        );
+
+        // the trigger thread only starts a new EventCache when it is handed a configuration
+        assert!(rescheduled.is_none());
+    }
+
+    #[test]
+    fn reload_reschedules_on_location_change() {
+        let rescheduled = reload_with(
+            "[automatic]\nlatitude = 52.5\nlongitude = 13.4\n",
+            "[automatic]\nlatitude = 48.1\nlongitude = 11.6\n",
+        );
+
+        assert_eq!(rescheduled.unwrap().automatic.unwrap().latitude, Some(48.1));
+    }
+
+    #[test]
+    fn reload_hands_resume_settings_to_trigger_thread() {
+        let location = "[automatic]\nlatitude = 52.5\nlongitude = 13.4\n";
+
+        let rescheduled = reload_with(
+            location,
+            &format!("catch_up_on_resume = true\nresume_delay_seconds = 10\n{location}"),
+        )
+        .unwrap();
+
+        assert!(rescheduled.catch_up_on_resume);
+        assert_eq!(rescheduled.resume_delay_seconds, 10);
+    }
+
+    #[test]
+    fn location_change_resets_pending_event() {
+        let now = "2025-06-21T12:00:00Z".parse().unwrap();
+        let actions = "[actions]\non_sunset = \"true\"\n";
+        let berlin = Configuration::parse(&format!(
+            "[automatic]\nlatitude = 52.5\nlongitude = 13.4\n{actions}"
+        ));
+        let oslo = Configuration::parse(&format!(
+            "[automatic]\nlatitude = 59.9\nlongitude = 10.7\n{actions}"
+        ));
+        let (scheduler, mut stale_cache) = schedule(berlin.trigger_settings()).unwrap();
+        scheduler.should_trigger(now, &mut stale_cache);
+        let berlin_sunset = stale_cache.pending().unwrap().at;
+
+        // what the trigger thread does when handed the new configuration
+        let (scheduler, mut cache) = schedule(oslo.trigger_settings()).unwrap();
+        let fired = scheduler.should_trigger(berlin_sunset, &mut cache);
+
+        assert_eq!(fired, None);
+        assert!(cache.pending().unwrap().at > berlin_sunset);
+        // keeping the cache would have run the action at Berlin's sunset
+        assert_eq!(
+            scheduler.should_trigger(berlin_sunset, &mut stale_cache),
+            Some((ActionTrigger::Sunset, berlin_sunset))
+        );
+    }
+
+    #[test]
+    fn stop_after_commands_is_clean() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(&path, "enabled = false").unwrap();
+        let (sender, receiver) = channel();
+        // the trigger thread is already gone
+        let (config_sender, _) = channel();
+        let daemon = Daemon {
+            watcher: None,
+            sender: sender.clone(),
+            receiver,
+            config_sender,
+            metrics: Metrics::default(),
+            running: RunningActions::default(),
+            pid_file: None,
+            trigger_thread: None,
+            listener: None,
+        };
+        for action in [
+            Action::Toggle,
+            Action::ReloadConfig,
+            Action::Enable,
+            Action::Stop,
+        ] {
+            sender.send(action.into()).unwrap();
+        }
+        let context =
+            Context::create_from_config(Configuration::default(), path.to_str().unwrap().into());
+
+        let result = daemon.run(context);
+
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn set_enabled_replies_with_state() {
+        let (sender, receiver) = channel();
+        let (config_sender, _) = channel();
+        let daemon = Daemon {
+            watcher: None,
+            sender: sender.clone(),
+            receiver,
+            config_sender,
+            metrics: Metrics::default(),
+            running: RunningActions::default(),
+            pid_file: None,
+            trigger_thread: None,
+            listener: None,
+        };
+        let (reply, responses) = channel();
+        for enabled in [false, false, true] {
+            sender
+                .send(Request {
+                    action: Action::SetEnabled(enabled),
+                    reply: Some(reply.clone()),
+                })
+                .unwrap();
+        }
+        sender.send(Action::Stop.into()).unwrap();
+
+        daemon
+            .run(Context::create_from_config(
+                Configuration::default(),
+                String::new(),
+            ))
+            .unwrap();
+
+        assert_eq!(
+            responses.try_iter().collect::<Vec<_>>(),
+            [
+                Response::Enabled(false),
+                Response::Enabled(false),
+                Response::Enabled(true)
+            ]
+        );
+    }
+
+    #[test]
+    fn query_info_survives_the_socket() {
+        let config = Configuration {
+            enabled: false,
+            manual: Some(
+                toml_edit::de::from_str(
+                    "[[time_stamps]]\ntrigger_time = \"12:00:00\"\naction = \"sunset\"\n",
+                )
+                .unwrap(),
+            ),
+            ..Default::default()
+        };
+        let now = DateTime::from_timestamp(1752414761, 0).unwrap();
+
+        let response = query_info(&config, now);
+        let encoded = bincode::encode_to_vec(&response, bincode::config::standard()).unwrap();
+        let (decoded, _): (Response, _) =
+            bincode::decode_from_slice(&encoded, bincode::config::standard()).unwrap();
+
+        assert_eq!(decoded, response);
+        let Response::Info(info) = decoded else {
+            panic!("expected info, got {response}");
+        };
+        assert!(!info.enabled);
+        assert_eq!(info.next_event.unwrap().trigger, ActionTrigger::Sunset);
+    }
+
+    #[test]
+    fn once_runs_current_action() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("ran");
+        let config = Configuration::parse(&format!(
+            "[[manual.time_stamps]]\ntrigger_time = \"00:00:00\"\naction = \"sunrise\"\n[actions]\non_sunrise = \"touch '{}'\"\n",
+            out.display()
+        ));
+
+        run_once(Context::create_from_config(config, String::new())).unwrap();
+
+        assert!(out.exists());
+    }
+
+    #[test]
+    fn once_runs_day_action_in_the_afternoon() {
+        let config = Configuration::parse(concat!(
+            "[automatic]\n",
+            "latitude = 49.598121\n",
+            "longitude = 11.003653\n",
+            "[actions]\n",
+            "on_sunrise = \"day\"\n",
+            "on_sunset = \"night\"\n",
+        ));
+        let afternoon = "2025-07-13T13:52:41Z".parse().unwrap();
+        let sunrise = TriggerSource::from_config(&config)
+            .unwrap()
+            .current_event_at(afternoon)
+            .unwrap()
+            .since
+            .unwrap();
+
+        let action = current_trigger(&config, afternoon).unwrap();
+
+        assert_eq!(
+            action,
+            Some(Action::Trigger {
+                trigger: ActionTrigger::Sunrise,
+                at: sunrise,
+                commands: vec![ActionCommand::Command("day".to_string())]
+            })
+        );
+    }
+
+    #[test]
+    fn stop_ends_trigger_thread() {
+        let (sender, receiver) = channel();
+        let (config_sender, config_receiver) = channel();
+        let trigger_thread = setup_trigger(sender.clone(), config_receiver).unwrap();
+        let daemon = Daemon {
+            watcher: None,
+            sender: sender.clone(),
+            receiver,
+            config_sender,
+            metrics: Metrics::default(),
+            running: RunningActions::default(),
+            pid_file: None,
+            trigger_thread: Some(trigger_thread),
+            listener: None,
+        };
+        sender.send(Action::Stop.into()).unwrap();
+        let start = Instant::now();
+
+        daemon
+            .run(Context::create_from_config(
+                Configuration::default(),
+                String::new(),
+            ))
+            .unwrap();
+
+        assert!(start.elapsed() < SHUTDOWN_TIMEOUT);
+    }
+
+    #[test]
+    fn stopped_listener_releases_socket() {
+        let name = "hyprsunrisewatcher-test-listener.sock"
+            .to_ns_name::<GenericNamespaced>()
+            .unwrap();
+        let socket = ListenerOptions::new()
+            .name(name.clone())
+            .create_sync()
+            .unwrap();
+        let (sender, _receiver) = channel();
+        let listener = ListenerThread::spawn(sender, socket, name.clone());
+
+        assert!(listener.stop(SHUTDOWN_TIMEOUT));
+        assert!(Stream::connect(name).is_err());
+    }
+
+    #[test]
+    fn join_gives_up_after_timeout() {
+        let (_keep, blocked) = channel::<()>();
+        let thread = std::thread::spawn(move || {
+            let _ = blocked.recv_timeout(Duration::from_secs(1));
+        });
+
+        assert!(!join_timeout(thread, Duration::from_millis(20)));
+        assert!(join_timeout(
+            std::thread::spawn(|| {}),
+            Duration::from_secs(1)
+        ));
+    }
+
+    fn event_at(at: DateTime<chrono::Utc>) -> PendingEvent {
+        PendingEvent {
+            trigger: ActionTrigger::Sunset,
+            at,
+            has_commands: false,
+        }
+    }
+
+    #[test]
+    fn wait_duration_sleeps_until_next_event() {
+        let now = DateTime::from_timestamp(1752414761, 0).unwrap();
+        let next = event_at(now + TimeDelta::minutes(5));
+
+        assert_eq!(wait_duration(now, Some(&next)), Duration::from_secs(300));
+    }
+
+    #[test]
+    fn clock_watch_notices_jumps() {
+        let wall = DateTime::from_timestamp(1752414761, 0).unwrap();
+        let monotonic = Instant::now();
+        let mut clock = ClockWatch::new(wall, monotonic);
+        let minutes = |m: u64| Duration::from_secs(m * 60);
+
+        let slept = clock.tick(wall + TimeDelta::minutes(30), monotonic + minutes(30));
+        // suspended for two hours, which the monotonic clock doesn't count
+        let resumed = clock.tick(wall + TimeDelta::minutes(150), monotonic + minutes(31));
+        let stepped_back = clock.tick(wall + TimeDelta::minutes(140), monotonic + minutes(32));
+
+        assert_eq!(slept, None);
+        assert_eq!(resumed, Some(TimeDelta::minutes(119)));
+        assert_eq!(stepped_back, Some(TimeDelta::minutes(-11)));
+    }
+
+    #[test]
+    fn resume_catches_up_with_last_missed_event() {
+        let actions: Actions =
+            toml_edit::de::from_str("on_sunset = \"light\"\non_dusk = \"dark\"").unwrap();
+        let location = LocationInfo::new(Coordinates::new(49.598121, 11.003653).unwrap());
+        let scheduler = Scheduler::automatic(location, actions);
+        let sunset = scheduler
+            .next_event_at(DateTime::from_timestamp(1752422400, 0).unwrap()) // 2025-07-13 16:00 UTC
+            .unwrap();
+        let dusk = scheduler.next_event_at(sunset.at).unwrap();
+        let suspended = sunset.at - TimeDelta::minutes(10);
+
+        let through_sunset = last_missed(&scheduler, suspended, sunset.at + TimeDelta::minutes(5));
+        let through_dusk = last_missed(&scheduler, suspended, dusk.at + TimeDelta::hours(1));
+        // nothing but solar midnight, which runs no action, passed
+        let later = last_missed(&scheduler, dusk.at, dusk.at + TimeDelta::hours(4));
+
+        assert_eq!(sunset.trigger, ActionTrigger::Sunset);
+        assert_eq!(through_sunset, Some((ActionTrigger::Sunset, sunset.at)));
+        assert_eq!(through_dusk, Some((ActionTrigger::Dusk, dusk.at)));
+        assert_eq!(later, None);
+    }
+
+    #[test]
+    fn delayed_catch_up_does_not_hold_up_park() {
+        let actions: Actions = toml_edit::de::from_str("on_sunset = \"light\"").unwrap();
+        let config = Configuration {
+            automatic: Some(
+                toml_edit::de::from_str("latitude = 49.598121\nlongitude = 11.003653").unwrap(),
+            ),
+            actions,
+            ..Default::default()
+        };
+        let scheduler = TriggerSource::from_config(&config).unwrap();
+        let sunset = scheduler
+            .next_event_at(DateTime::from_timestamp(1752422400, 0).unwrap()) // 2025-07-13 16:00 UTC
+            .unwrap();
+        let (sender, requests) = channel();
+        let (_config_sender, configs) = channel();
+        let start = Instant::now();
+
+        let caught_up = catch_up_missed(
+            &scheduler,
+            sunset.at - TimeDelta::minutes(10),
+            sunset.at + TimeDelta::minutes(5),
+            &sender,
+            Duration::from_millis(300),
+        );
+        let wake = park(&configs, Duration::from_millis(10));
+
+        assert_eq!(caught_up, Some(ActionTrigger::Sunset));
+        assert!(matches!(wake, Wake::Timeout));
+        assert!(start.elapsed() < Duration::from_millis(200));
+        assert!(requests.try_recv().is_err());
+        let request = requests.recv_timeout(Duration::from_secs(5)).unwrap();
+        // the action is told when the sun set, not when it was caught up on
+        assert!(matches!(
+            request.action,
+            Action::Trigger {
+                trigger: ActionTrigger::Sunset,
+                at,
+                ..
+            } if at == sunset.at
+        ));
+        assert!(start.elapsed() >= Duration::from_millis(300));
+    }
+
+    #[test]
+    fn until_midnight_wakes_at_day_change() {
+        let evening = Local.with_ymd_and_hms(2025, 7, 13, 23, 30, 0).unwrap();
+
+        assert_eq!(until_midnight(evening), Duration::from_secs(30 * 60));
+    }
+
+    #[test]
+    fn until_midnight_wakes_when_the_clock_skips_it() {
+        // Chile starts daylight saving time at midnight, the day starts at 01:00
+        let evening = chrono_tz::America::Santiago
+            .with_ymd_and_hms(2025, 9, 6, 22, 0, 0)
+            .unwrap();
+
+        assert_eq!(until_midnight(evening), Duration::from_secs(2 * 60 * 60));
+    }
+
+    #[test]
+    fn wait_duration_is_capped() {
+        let now = DateTime::from_timestamp(1752414761, 0).unwrap();
+        let next = event_at(now + TimeDelta::hours(5));
+
+        assert_eq!(wait_duration(now, Some(&next)), MAX_SLEEP);
+        assert_eq!(wait_duration(now, None), MAX_SLEEP);
+    }
+
+    #[test]
+    fn park_wakes_on_config_and_disconnect() {
+        let (sender, receiver) = channel();
+        sender.send(Arc::new(Configuration::default())).unwrap();
+        let start = Instant::now();
+
+        assert!(matches!(park(&receiver, MAX_SLEEP), Wake::Config(_)));
+        assert!(start.elapsed() < Duration::from_secs(1));
+        assert!(matches!(
+            park(&receiver, Duration::from_millis(1)),
+            Wake::Timeout
+        ));
+        drop(sender);
+        assert!(matches!(park(&receiver, MAX_SLEEP), Wake::Disconnected));
+    }
+
+    #[test]
+    fn debounce_reloads_once_per_burst() {
+        let (modified, events) = channel();
+        let window = Duration::from_millis(50);
+        let burst = || {
+            for _ in 0..3 {
+                modified.send(()).unwrap();
+            }
+        };
+        let reloads = std::thread::spawn(move || {
+            let mut reloads = 0;
+            debounce(&events, window, || {
+                reloads += 1;
+                true
+            });
+            reloads
+        });
+
+        burst();
+        std::thread::sleep(window * 4);
+        burst();
+        std::thread::sleep(window * 4);
+        drop(modified);
+
+        assert_eq!(reloads.join().unwrap(), 2);
+    }
+
+    #[test]
+    fn hot_reload_survives_rename_over_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path();
+        let config = dir.join("config.toml");
+        std::fs::write(&config, "enabled = true").unwrap();
+        let (sender, receiver) = channel();
+        let watcher = start_hot_reload(config.clone(), sender, Duration::from_millis(50)).unwrap();
+        let save = |content: &str| {
+            // what vim does with `backupcopy=no`
+            let swap = dir.join("config.toml~");
+            std::fs::write(&swap, content).unwrap();
+            std::fs::rename(&swap, &config).unwrap();
+        };
+        let reloaded = || {
+            receiver
+                .recv_timeout(Duration::from_secs(5))
+                .is_ok_and(|request| request.action == Action::ReloadConfig)
+        };
+
+        save("enabled = false");
+        assert!(reloaded());
+        save("enabled = true");
+        assert!(reloaded());
+
+        drop(watcher);
+    }
+
+    #[test]
+    fn fire_guard_trips_on_runaway_loop() {
+        let now = Instant::now();
+        let mut guard = FireGuard::new(now);
+
+        for _ in 0..MAX_FIRES_PER_SECOND {
+            assert!(!guard.record(now));
+        }
+
+        assert!(guard.record(now));
+    }
+
+    #[test]
+    fn fire_guard_resets_after_a_second() {
+        let now = Instant::now();
+        let mut guard = FireGuard::new(now);
+        for _ in 0..MAX_FIRES_PER_SECOND {
+            guard.record(now);
+        }
+
+        assert!(!guard.record(now + Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn reload_gives_up_on_missing_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("missing.toml");
+
+        let reloaded = reload_config(path.to_str().unwrap(), 3, Duration::from_millis(1));
+
+        assert!(reloaded.is_none());
+    }
+
+    #[test]
+    fn reload_loads_existing_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(&path, "enabled = false").unwrap();
+
+        let reloaded = reload_config(path.to_str().unwrap(), 3, Duration::from_millis(1));
+
+        assert!(!reloaded.unwrap().enabled);
+    }
+
+    #[test]
+    fn caught_up_action_sees_scheduled_event_time() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("event_time");
+        let mut config = Arc::new(Configuration::parse(concat!(
+            "[[manual.time_stamps]]\n",
+            "trigger_time = \"07:00:00\"\n",
+            "action = \"Sunrise\"\n",
+        )));
+        let (sender, receiver) = channel();
+        let (config_sender, _config_receiver) = channel();
+        let daemon = Daemon {
+            watcher: None,
+            sender,
+            receiver,
+            config_sender,
+            metrics: Metrics::default(),
+            running: RunningActions::default(),
+            pid_file: None,
+            trigger_thread: None,
+            listener: None,
+        };
+        let sunrise = DateTime::from_timestamp(1752382800, 0).unwrap(); // 2025-07-13 05:00 UTC
+
+        handle_command(
+            Action::Trigger {
+                trigger: ActionTrigger::Sunrise,
+                at: sunrise,
+                commands: vec![ActionCommand::Command(format!(
+                    "printf %s \"$HSW_EVENT_TIME\" > {}",
+                    out.display()
+                ))],
+            },
+            &mut config,
+            daemon,
+            "",
+        )
+        .unwrap();
+        let start = Instant::now();
+        while std::fs::read_to_string(&out).map_or(true, |time| time.is_empty())
+            && start.elapsed() < Duration::from_secs(5)
+        {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        assert_eq!(
+            std::fs::read_to_string(&out).unwrap(),
+            "2025-07-13T05:00:00+00:00"
+        );
+    }
+
+    #[test]
+    fn reload_keeps_config_when_new_one_is_invalid() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(&path, "enabled = \"yes\"").unwrap();
+        let mut config = Arc::new(Configuration::default());
+        let old = Arc::clone(&config);
+        let (sender, receiver) = channel();
+        let (config_sender, config_receiver) = channel();
+        let daemon = Daemon {
+            watcher: None,
+            sender,
+            receiver,
+            config_sender,
+            metrics: Metrics::default(),
+            running: RunningActions::default(),
+            pid_file: None,
+            trigger_thread: None,
+            listener: None,
+        };
+
+        let daemon = handle_command(
+            Action::ReloadConfig,
+            &mut config,
+            daemon,
+            path.to_str().unwrap(),
+        );
+
+        assert!(daemon.is_ok());
+        assert!(Arc::ptr_eq(&config, &old));
+        assert!(config_receiver.try_recv().is_err());
+    }
+}
@@ -0,0 +1,115 @@
+use chrono::{
+    DateTime, Days, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta, TimeZone, Utc,
+};
+
+use crate::error::Error;
+
+/// Parses `today`, `tomorrow`, `yesterday`, `+Nd`, `-Nd` or `YYYY-MM-DD` relative to `today`
+pub fn parse_date(s: &str, today: NaiveDate) -> Result<NaiveDate, Error> {
+    let date = match s.to_ascii_lowercase().as_str() {
+        "today" => Some(today),
+        "tomorrow" => today.succ_opt(),
+        "yesterday" => today.pred_opt(),
+        other => match other.split_at_checked(1) {
+            Some(("+", days)) => days_offset(days).and_then(|days| today.checked_add_days(days)),
+            Some(("-", days)) => days_offset(days).and_then(|days| today.checked_sub_days(days)),
+            _ => NaiveDate::parse_from_str(other, "%Y-%m-%d").ok(),
+        },
+    };
+    date.ok_or_else(|| Error::InvalidDate(s.to_string()))
+}
+
+fn days_offset(s: &str) -> Option<Days> {
+    s.strip_suffix('d')?.parse().ok().map(Days::new)
+}
+
+/// clap value parser for `--date`, relative dates are resolved against the local date
+pub fn parse_date_arg(s: &str) -> Result<NaiveDate, Error> {
+    parse_date(s, Local::now().date_naive())
+}
+
+/// The instant the clock of `tz` shows `wall_clock`, the first of both if it shows it twice as
+/// daylight saving time ends. An hour later if the clock skips it as daylight saving time starts.
+pub fn local_instant<Tz: TimeZone>(wall_clock: NaiveDateTime, tz: &Tz) -> Option<DateTime<Tz>> {
+    tz.from_local_datetime(&wall_clock).earliest().or_else(|| {
+        tz.from_local_datetime(&(wall_clock + TimeDelta::hours(1)))
+            .earliest()
+    })
+}
+
+/// The instant events are computed from: now for today, otherwise the start of the local day
+pub fn reference_time(date: Option<NaiveDate>) -> DateTime<Utc> {
+    match date {
+        Some(date) if date != Local::now().date_naive() => date
+            .and_time(NaiveTime::MIN)
+            .and_local_timezone(Local)
+            .earliest()
+            .map(|start| start.with_timezone(&Utc))
+            .unwrap_or_else(Utc::now),
+        _ => Utc::now(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{NaiveDate, Utc};
+    use chrono_tz::Europe::Berlin;
+
+    use super::{local_instant, parse_date};
+
+    fn today() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2025, 12, 31).unwrap()
+    }
+
+    #[test]
+    fn parses_relative_dates() {
+        assert_eq!(parse_date("today", today()).unwrap(), today());
+        assert_eq!(
+            parse_date("Tomorrow", today()).unwrap(),
+            NaiveDate::from_ymd_opt(2026, 1, 1).unwrap()
+        );
+        assert_eq!(
+            parse_date("yesterday", today()).unwrap(),
+            NaiveDate::from_ymd_opt(2025, 12, 30).unwrap()
+        );
+        assert_eq!(
+            parse_date("+3d", today()).unwrap(),
+            NaiveDate::from_ymd_opt(2026, 1, 3).unwrap()
+        );
+        assert_eq!(
+            parse_date("-31d", today()).unwrap(),
+            NaiveDate::from_ymd_opt(2025, 11, 30).unwrap()
+        );
+    }
+
+    #[test]
+    fn parses_absolute_dates() {
+        assert_eq!(
+            parse_date("2025-06-21", today()).unwrap(),
+            NaiveDate::from_ymd_opt(2025, 6, 21).unwrap()
+        );
+    }
+
+    #[test]
+    fn local_instant_moves_past_skipped_time() {
+        let day = NaiveDate::from_ymd_opt(2025, 3, 30).unwrap();
+
+        let skipped = local_instant(day.and_hms_opt(2, 30, 0).unwrap(), &Berlin).unwrap();
+        let shown = local_instant(day.and_hms_opt(1, 30, 0).unwrap(), &Berlin).unwrap();
+
+        assert_eq!(skipped.to_rfc3339(), "2025-03-30T03:30:00+02:00");
+        assert_eq!(
+            (skipped.with_timezone(&Utc) - shown.with_timezone(&Utc)).num_hours(),
+            1
+        );
+    }
+
+    #[test]
+    fn rejects_unparseable_dates() {
+        for date in ["soon", "+3", "+xd", "2025-13-01", ""] {
+            let err = parse_date(date, today()).unwrap_err();
+
+            assert_eq!(err.to_string(), format!("Invalid date: {date}"));
+        }
+    }
+}
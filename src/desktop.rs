@@ -0,0 +1,52 @@
+use std::process::Command;
+
+use chrono::{DateTime, Local};
+
+use crate::scheduler::ActionTrigger;
+
+/// Shows a desktop notification that `trigger` fired, through `notify-send`
+pub fn notify_trigger(trigger: ActionTrigger, at: DateTime<Local>, location: Option<&str>) {
+    let child = Command::new("notify-send")
+        .args(["--app-name", "hyprsunrisewatcher", "--urgency", "low"])
+        .arg(trigger.to_string())
+        .arg(body(trigger, at, location))
+        .spawn();
+    match child {
+        // reaped in the background so a hanging notification daemon can't block triggers
+        Ok(mut child) => {
+            std::thread::spawn(move || child.wait());
+        }
+        Err(err) => log::warn!("Failed to run notify-send: {err}"),
+    }
+}
+
+fn body(trigger: ActionTrigger, at: DateTime<Local>, location: Option<&str>) -> String {
+    let mut body = format!("Ran the {trigger} actions at {}", at.format("%H:%M"));
+    if let Some(location) = location {
+        body.push_str(&format!(" in {location}"));
+    }
+    body
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{Local, TimeZone};
+
+    use crate::scheduler::ActionTrigger;
+
+    use super::body;
+
+    #[test]
+    fn body_names_trigger_time_and_location() {
+        let at = Local.with_ymd_and_hms(2025, 7, 13, 21, 32, 10).unwrap();
+
+        assert_eq!(
+            body(ActionTrigger::Sunset, at, None),
+            "Ran the sunset actions at 21:32"
+        );
+        assert_eq!(
+            body(ActionTrigger::Dusk, at, Some("Berlin")),
+            "Ran the dusk actions at 21:32 in Berlin"
+        );
+    }
+}
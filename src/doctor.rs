@@ -0,0 +1,128 @@
+use chrono::{Datelike, Local, NaiveDate};
+use sunrise::Coordinates;
+
+use crate::{
+    context::Context,
+    lint::lint_actions,
+    scheduler::{ActionTrigger, LocationInfo},
+};
+
+/// The days on which `trigger` doesn't happen at all
+#[derive(Debug, PartialEq, Eq)]
+pub struct Gap {
+    pub trigger: ActionTrigger,
+    pub days: Vec<NaiveDate>,
+}
+
+/// Finds the events that don't happen on some of `days` at `location`, e.g. no sunset during
+/// polar day
+pub fn polar_gaps(location: &LocationInfo, days: impl Iterator<Item = NaiveDate>) -> Vec<Gap> {
+    let mut gaps: Vec<Gap> = vec![];
+    for day in days {
+        for trigger in location.solar_times(day).missing(day) {
+            match gaps.iter_mut().find(|gap| gap.trigger == trigger) {
+                Some(gap) => gap.days.push(day),
+                None => gaps.push(Gap {
+                    trigger,
+                    days: vec![day],
+                }),
+            }
+        }
+    }
+    gaps
+}
+
+/// Checks the configuration for problems that only show up at runtime
+pub struct Doctor;
+
+impl Doctor {
+    pub fn run(self, context: Context) -> crate::error::Result<()> {
+        context.config.validate()?;
+        let mut warnings = 0;
+        for finding in lint_actions(&context.config.actions) {
+            println!("warning: {finding}");
+            warnings += 1;
+        }
+        if let Some(auto) = &context.config.automatic
+            && let Ok((latitude, longitude)) = auto.coordinates()
+            && let Some(coords) = Coordinates::new(latitude, longitude)
+        {
+            let first = Local::now().date_naive().with_day(1).unwrap();
+            let month = first.iter_days().take_while(|d| d.month() == first.month());
+            for gap in polar_gaps(
+                &LocationInfo::new(coords)
+                    .with_twilight(auto.twilight)
+                    .with_fallback(auto.fallback),
+                month,
+            ) {
+                println!(
+                    "warning: no {} on {} day(s) this month ({} to {}), its action will be skipped",
+                    gap.trigger,
+                    gap.days.len(),
+                    gap.days.first().unwrap(),
+                    gap.days.last().unwrap()
+                );
+                warnings += 1;
+            }
+        }
+        if warnings == 0 {
+            println!("No problems found");
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::NaiveDate;
+    use sunrise::Coordinates;
+
+    use crate::{
+        config::Twilight,
+        scheduler::{ActionTrigger, LocationInfo},
+    };
+
+    use super::polar_gaps;
+
+    fn june() -> impl Iterator<Item = NaiveDate> {
+        NaiveDate::from_ymd_opt(2025, 6, 1)
+            .unwrap()
+            .iter_days()
+            .take(30)
+    }
+
+    #[test]
+    fn no_gaps_at_mid_latitudes() {
+        let location = LocationInfo::new(Coordinates::new(49.598121, 11.003653).unwrap());
+
+        assert!(polar_gaps(&location, june()).is_empty());
+    }
+
+    #[test]
+    fn polar_day_has_gaps() {
+        let location = LocationInfo::new(Coordinates::new(78.0, 15.0).unwrap());
+
+        let gaps = polar_gaps(&location, june());
+
+        let sunset = gaps
+            .iter()
+            .find(|gap| gap.trigger == ActionTrigger::Sunset)
+            .unwrap();
+        assert_eq!(sunset.days.len(), 30);
+    }
+
+    #[test]
+    fn nautical_twilight_missing_in_white_nights() {
+        let coords = Coordinates::new(60.0, 10.0).unwrap();
+
+        let civil = polar_gaps(&LocationInfo::new(coords), june());
+        let nautical = polar_gaps(
+            &LocationInfo::new(coords).with_twilight(Twilight::Nautical),
+            june(),
+        );
+
+        assert!(civil.is_empty());
+        let triggers = nautical.iter().map(|gap| gap.trigger).collect::<Vec<_>>();
+        assert_eq!(triggers, [ActionTrigger::Dawn, ActionTrigger::Dusk]);
+    }
+}
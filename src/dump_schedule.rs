@@ -0,0 +1,85 @@
+use chrono::{Local, NaiveDate, TimeZone};
+use sunrise::Coordinates;
+
+use crate::{config::Twilight, context::Context, scheduler::SolarTimes};
+
+/// Prints the solar events of a day at the configured coordinates, to check they are right
+pub struct ScheduleDump {
+    date: NaiveDate,
+}
+
+impl ScheduleDump {
+    pub fn new(date: NaiveDate) -> Self {
+        Self { date }
+    }
+
+    pub fn run(self, context: Context) -> crate::error::Result<()> {
+        let Some(auto) = &context.config.automatic else {
+            return Err(crate::error::Error::InvalidConfiguration(
+                "dump-schedule requires an [automatic] location".to_string(),
+            ));
+        };
+        let (latitude, longitude) = auto.coordinates()?;
+        let coords = Coordinates::new(latitude, longitude)
+            .ok_or(crate::error::Error::InvalidCoordinates(latitude, longitude))?;
+        print!("{}", render(coords, self.date, auto.twilight, &Local));
+        Ok(())
+    }
+}
+
+/// The events of `date` as a table in `tz`, computed without offsets or fallback times
+fn render<Tz: TimeZone>(coords: Coordinates, date: NaiveDate, twilight: Twilight, tz: &Tz) -> String
+where
+    Tz::Offset: std::fmt::Display,
+{
+    let times = SolarTimes::compute(coords, date, twilight);
+    let mut out = format!(
+        "{date} at lat: {} long: {}, {twilight} twilight\n",
+        coords.lat(),
+        coords.lon()
+    );
+    if let Some(polar) = times.polar(date, coords.lat()) {
+        out.push_str(&format!("{polar}\n"));
+    }
+    for (event, at) in [
+        ("dawn", times.dawn),
+        ("sunrise", times.sunrise),
+        ("sunset", times.sunset),
+        ("dusk", times.dusk),
+    ] {
+        out.push_str(&format!(
+            "{event:<8} {}\n",
+            at.with_timezone(tz).format("%H:%M:%S %:z")
+        ));
+    }
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{FixedOffset, NaiveDate};
+    use sunrise::Coordinates;
+
+    use crate::config::Twilight;
+
+    use super::render;
+
+    #[test]
+    fn berlin_midsummer_table() {
+        let berlin = Coordinates::new(52.52, 13.405).unwrap();
+        let cest = FixedOffset::east_opt(2 * 3600).unwrap();
+        let date = NaiveDate::from_ymd_opt(2025, 6, 21).unwrap();
+
+        let table = render(berlin, date, Twilight::Nautical, &cest);
+        let lines = table.lines().collect::<Vec<_>>();
+
+        assert_eq!(
+            lines[0],
+            "2025-06-21 at lat: 52.52 long: 13.405, nautical twilight"
+        );
+        assert_eq!(lines.len(), 5);
+        assert!(lines[2].starts_with("sunrise  04:4"));
+        assert!(lines[3].starts_with("sunset   21:3"));
+        assert!(lines[4].ends_with(" +02:00"));
+    }
+}
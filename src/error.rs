@@ -1,12 +1,96 @@
+use std::sync::mpsc::SendError;
+
 #[derive(Debug)]
 pub enum Error {
     InvalidCoordinates(f64, f64),
     InvalidAction(String),
-    InvalidConfiguration,
-    FailedtoCreateDaemon,
+    InvalidConfiguration(String),
+    ConfigNotFound(String),
+    InvalidPath(String, String),
+    Serialization(String, String),
+    InvalidEventCache(String),
+    InvalidDate(String),
+    DaemonNotRunning,
+    DaemonAlreadyRunning,
+    ReloadRejected(String),
+    NotConfirmed(String),
+    Io(std::io::Error),
+    Config(Box<figment::Error>),
+    Toml(toml_edit::TomlError),
+    Json(serde_json::Error),
+    Protocol(String),
+    Watch(notify::Error),
+    SignalHandler(ctrlc::Error),
+    ChannelClosed,
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            Error::Config(err) => Some(err),
+            Error::Toml(err) => Some(err),
+            Error::Json(err) => Some(err),
+            Error::Watch(err) => Some(err),
+            Error::SignalHandler(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+impl From<figment::Error> for Error {
+    fn from(err: figment::Error) -> Self {
+        Error::Config(Box::new(err))
+    }
+}
+
+impl From<toml_edit::TomlError> for Error {
+    fn from(err: toml_edit::TomlError) -> Self {
+        Error::Toml(err)
+    }
 }
 
-impl std::error::Error for Error {}
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Self {
+        Error::Json(err)
+    }
+}
+
+impl From<bincode::error::EncodeError> for Error {
+    fn from(err: bincode::error::EncodeError) -> Self {
+        Error::Protocol(err.to_string())
+    }
+}
+
+impl From<bincode::error::DecodeError> for Error {
+    fn from(err: bincode::error::DecodeError) -> Self {
+        Error::Protocol(err.to_string())
+    }
+}
+
+impl From<notify::Error> for Error {
+    fn from(err: notify::Error) -> Self {
+        Error::Watch(err)
+    }
+}
+
+impl From<ctrlc::Error> for Error {
+    fn from(err: ctrlc::Error) -> Self {
+        Error::SignalHandler(err)
+    }
+}
+
+impl<T> From<SendError<T>> for Error {
+    fn from(_: SendError<T>) -> Self {
+        Error::ChannelClosed
+    }
+}
 
 impl std::fmt::Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -15,10 +99,103 @@ impl std::fmt::Display for Error {
                 f.write_str(&format!("Invalid Coordinates - lat: {lat} long: {long}",))
             }
             Error::InvalidAction(action) => f.write_str(&format!("Invalid action: {action}")),
-            Error::InvalidConfiguration => f.write_str("Invalid configuration"),
-            Error::FailedtoCreateDaemon => todo!(),
+            Error::InvalidConfiguration(reason) => {
+                f.write_str(&format!("Invalid configuration: {reason}"))
+            }
+            Error::ConfigNotFound(path) => f.write_str(&format!("Config file {path} not found")),
+            Error::InvalidPath(field, reason) => {
+                f.write_str(&format!("Invalid path for {field}: {reason}"))
+            }
+            Error::Serialization(what, reason) => {
+                f.write_str(&format!("Failed to serialize {what}: {reason}"))
+            }
+            Error::InvalidEventCache(reason) => {
+                f.write_str(&format!("Invalid event cache: {reason}"))
+            }
+            Error::InvalidDate(date) => f.write_str(&format!("Invalid date: {date}")),
+            Error::DaemonNotRunning => f.write_str("No daemon is running"),
+            Error::DaemonAlreadyRunning => f.write_str("A daemon is already running"),
+            Error::ReloadRejected(reason) => f.write_str(&format!("Reload rejected: {reason}")),
+            Error::NotConfirmed(question) => f.write_str(&format!("Aborted: {question}")),
+            Error::Io(err) => err.fmt(f),
+            Error::Config(err) => err.fmt(f),
+            Error::Toml(err) => err.fmt(f),
+            Error::Json(err) => err.fmt(f),
+            Error::Protocol(reason) => f.write_str(&format!("Protocol error: {reason}")),
+            Error::Watch(err) => f.write_str(&format!("Failed to watch config file: {err}")),
+            Error::SignalHandler(err) => {
+                f.write_str(&format!("Failed to set up signal handler: {err}"))
+            }
+            Error::ChannelClosed => f.write_str("Daemon channel closed"),
         }
     }
 }
 
-pub type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[cfg(test)]
+mod test {
+    use super::Error;
+
+    #[test]
+    fn messages_name_the_problem() {
+        let cases = [
+            (
+                Error::InvalidCoordinates(91.0, 0.0),
+                "Invalid Coordinates - lat: 91 long: 0",
+            ),
+            (
+                Error::InvalidAction("zenith".to_string()),
+                "Invalid action: zenith",
+            ),
+            (
+                Error::InvalidConfiguration("shell is empty".to_string()),
+                "Invalid configuration: shell is empty",
+            ),
+            (
+                Error::ConfigNotFound("/etc/hyprsunrisewatcher.toml".to_string()),
+                "Config file /etc/hyprsunrisewatcher.toml not found",
+            ),
+            (
+                Error::InvalidPath("pid_file".to_string(), "unset variable".to_string()),
+                "Invalid path for pid_file: unset variable",
+            ),
+            (
+                Error::Serialization("configuration".to_string(), "unsupported".to_string()),
+                "Failed to serialize configuration: unsupported",
+            ),
+            (
+                Error::InvalidEventCache("wrong location".to_string()),
+                "Invalid event cache: wrong location",
+            ),
+            (
+                Error::InvalidDate("someday".to_string()),
+                "Invalid date: someday",
+            ),
+            (Error::DaemonNotRunning, "No daemon is running"),
+            (Error::DaemonAlreadyRunning, "A daemon is already running"),
+            (
+                Error::ReloadRejected("validator failed".to_string()),
+                "Reload rejected: validator failed",
+            ),
+            (
+                Error::NotConfirmed("Overwrite?".to_string()),
+                "Aborted: Overwrite?",
+            ),
+            (Error::Io(std::io::Error::other("disk full")), "disk full"),
+            (
+                Error::Protocol("unexpected end".to_string()),
+                "Protocol error: unexpected end",
+            ),
+            (
+                Error::Watch(notify::Error::generic("no inotify")),
+                "Failed to watch config file: no inotify",
+            ),
+            (Error::ChannelClosed, "Daemon channel closed"),
+        ];
+
+        for (error, message) in cases {
+            assert_eq!(error.to_string(), message);
+        }
+    }
+}
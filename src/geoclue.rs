@@ -0,0 +1,99 @@
+use std::{
+    io::{self, BufRead, BufReader},
+    path::Path,
+    process::{Command, Stdio},
+    sync::Mutex,
+};
+
+/// Where distributions install the GeoClue demo agent
+const WHERE_AM_I: [&str; 3] = [
+    "/usr/libexec/geoclue-2.0/demos/where-am-i",
+    "/usr/lib/geoclue-2.0/demos/where-am-i",
+    "/usr/lib64/geoclue-2.0/demos/where-am-i",
+];
+/// Seconds `where-am-i` waits for a location before giving up
+const TIMEOUT_SECS: &str = "10";
+
+/// The last lookup, a failed one included so it isn't retried on every event
+static DETECTED: Mutex<Option<Option<(f64, f64)>>> = Mutex::new(None);
+
+/// The `(latitude, longitude)` of the device according to GeoClue, looked up once until
+/// [`forget`] is called. Logs a warning and returns `None` if GeoClue can't tell.
+pub fn locate() -> Option<(f64, f64)> {
+    if let Some(detected) = *DETECTED.lock().unwrap() {
+        return detected;
+    }
+    // not holding the lock while `where-am-i` runs, concurrent callers may both look it up
+    let detected = lookup()
+        .inspect(|(latitude, longitude)| {
+            log::info!("GeoClue located the device at lat: {latitude} long: {longitude}")
+        })
+        .inspect_err(|err| log::warn!("Failed to get the location from GeoClue: {err}"))
+        .ok();
+    *DETECTED.lock().unwrap() = Some(detected);
+    detected
+}
+
+/// Drops the last lookup, so the next [`locate`] asks GeoClue again
+pub fn forget() {
+    *DETECTED.lock().unwrap() = None;
+}
+
+fn lookup() -> io::Result<(f64, f64)> {
+    let program = WHERE_AM_I
+        .into_iter()
+        .find(|path| Path::new(path).exists())
+        .ok_or_else(|| io::Error::other("where-am-i of geoclue-2.0 is not installed"))?;
+    let mut child = Command::new(program)
+        .args(["--timeout", TIMEOUT_SECS])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+    let stdout = child.stdout.take().expect("stdout is piped");
+    let mut lines = BufReader::new(stdout).lines().map_while(Result::ok);
+    let coordinates = parse(&mut lines);
+    // keeps reporting updates until the timeout otherwise
+    let _ = child.kill();
+    child.wait()?;
+    coordinates.ok_or_else(|| io::Error::other("no location within the timeout"))
+}
+
+/// Reads the first complete location from the output of `where-am-i`
+fn parse(lines: impl Iterator<Item = String>) -> Option<(f64, f64)> {
+    let degrees = |value: &str| value.trim().trim_end_matches('°').parse::<f64>().ok();
+    let mut latitude = None;
+    for line in lines {
+        if let Some(value) = line.trim().strip_prefix("Latitude:") {
+            latitude = degrees(value);
+        } else if let Some(value) = line.trim().strip_prefix("Longitude:")
+            && let (Some(latitude), Some(longitude)) = (latitude, degrees(value))
+        {
+            return Some((latitude, longitude));
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use super::parse;
+
+    #[test]
+    fn location_is_read_from_where_am_i() {
+        let output = concat!(
+            "Client object: /org/freedesktop/GeoClue2/Client/1\n",
+            "\n",
+            "New location:\n",
+            "Latitude:    52.520008°\n",
+            "Longitude:   13.404954°\n",
+            "Accuracy:    25000.000000 meters\n",
+            "Description: WiFi\n",
+        );
+
+        assert_eq!(
+            parse(output.lines().map(str::to_string)),
+            Some((52.520008, 13.404954))
+        );
+        assert_eq!(parse("Latitude: 52.5°\n".lines().map(str::to_string)), None);
+    }
+}
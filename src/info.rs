@@ -1,66 +1,449 @@
 use std::fmt::Display;
 
-use chrono::{DateTime, Local, Utc};
-use serde::Serialize;
+use bincode::{Decode, Encode};
+use chrono::{DateTime, Local, TimeDelta, TimeZone, Utc};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize, Serializer};
 
-use crate::{config::Configuration, context::Context, scheduler::ActionTrigger};
+use crate::{
+    actions::{Action, Response},
+    client,
+    config::{ConfigSources, Configuration, Origin},
+    context::Context,
+    logging,
+    scheduler::{ActionTrigger, Polar},
+};
 
-#[derive(Serialize, PartialEq, Eq, Debug)]
+#[derive(Serialize, Deserialize, Encode, Decode, PartialEq, Eq, Debug, Clone)]
 pub struct EventInfo {
+    #[bincode(with_serde)]
     pub at: DateTime<Utc>,
     pub trigger: ActionTrigger,
     pub action: Option<String>,
+    /// Name of the location the event was computed for
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub location: Option<String>,
+    /// Set if the sun doesn't cross the horizon today, which is why the event is far off
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub polar: Option<Polar>,
 }
 
-#[derive(Serialize)]
-pub struct Info<'a> {
-    pub next_event: Option<EventInfo>,
-    pub configuration: &'a Configuration,
+impl EventInfo {
+    /// Time left until the event, negative once it has passed
+    pub fn time_until(&self, now: DateTime<Utc>) -> TimeDelta {
+        self.at - now
+    }
+
+    /// The time until the event for humans, e.g. `in 2h5m`, or `now` if it is due or just passed
+    pub fn countdown(&self, now: DateTime<Utc>) -> String {
+        match self.time_until(now).to_std() {
+            Ok(left) if !left.is_zero() => format!("in {}", logging::duration(left)),
+            _ => "now".to_string(),
+        }
+    }
 }
 
-impl Display for EventInfo {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_str("At: ")?;
-        self.at.with_timezone(&Local).fmt(f)?;
+/// The next event as seen at `now`, as JSON along with the seconds left until it
+#[derive(Debug, Clone)]
+pub struct NextEvent {
+    pub event: EventInfo,
+    pub now: DateTime<Utc>,
+}
 
-        f.write_str("Action: ")?;
-        if let Some(a) = &self.action {
-            a.fmt(f)?;
+impl Serialize for NextEvent {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        #[derive(Serialize)]
+        struct Timed<'a> {
+            #[serde(flatten)]
+            event: &'a EventInfo,
+            seconds_until: i64,
+        }
+        Timed {
+            event: &self.event,
+            seconds_until: self.event.time_until(self.now).num_seconds().max(0),
         }
-        f.write_str("Trigger: ")?;
+        .serialize(serializer)
+    }
+}
+
+impl Display for NextEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.event.describe(self.now, &Local))
+    }
+}
+
+/// How commands print their result
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Format {
+    #[default]
+    Text,
+    Json,
+    /// A single line for a Waybar custom module
+    Waybar,
+}
+
+/// Status of a Waybar custom module with `"return-type": "json"`
+#[derive(Serialize, Debug, PartialEq, Eq)]
+pub struct WaybarStatus {
+    pub text: String,
+    pub tooltip: String,
+    pub class: &'static str,
+}
+
+/// The event whose period a point in time falls into
+#[derive(Serialize, Deserialize, Encode, Decode, PartialEq, Eq, Debug, Clone, Copy)]
+pub struct CurrentEvent {
+    pub trigger: ActionTrigger,
+    /// Unknown during polar day or night
+    #[bincode(with_serde)]
+    pub since: Option<DateTime<Utc>>,
+}
+
+/// What the running daemon reports about itself for `info`
+#[derive(Serialize, Deserialize, Encode, Decode, PartialEq, Eq, Debug, Clone)]
+pub struct DaemonInfo {
+    pub current_event: Option<CurrentEvent>,
+    pub next_event: Option<EventInfo>,
+    pub enabled: bool,
+    /// When the daemon looked the events up
+    #[bincode(with_serde)]
+    pub now: DateTime<Utc>,
+}
+
+impl Display for CurrentEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         self.trigger.fmt(f)?;
+        if let Some(since) = self.since {
+            f.write_str(&format!(
+                " since {}",
+                since.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S")
+            ))?;
+        }
         Ok(())
     }
 }
 
+#[derive(Serialize)]
+pub struct Info<'a> {
+    pub current_event: Option<CurrentEvent>,
+    pub next_event: Option<NextEvent>,
+    /// Whether the events and `enabled` are those of the running daemon
+    pub daemon: bool,
+    /// Which file the configuration was read from
+    pub sources: ConfigSources,
+    pub configuration: &'a Configuration,
+}
+
+impl EventInfo {
+    /// One line per detail with the time in `tz` and the time left as of `now`, leaving out the
+    /// unset ones
+    pub fn describe<Tz: TimeZone>(&self, now: DateTime<Utc>, tz: &Tz) -> String
+    where
+        Tz::Offset: Display,
+    {
+        let mut out = format!(
+            "At: {} ({})\nTrigger: {}",
+            self.at.with_timezone(tz).format("%Y-%m-%d %H:%M:%S %:z"),
+            self.countdown(now),
+            self.trigger
+        );
+        if let Some(polar) = &self.polar {
+            out.push_str(&format!(" ({polar})"));
+        }
+        if let Some(action) = &self.action {
+            out.push_str(&format!("\nAction: {action}"));
+        }
+        if let Some(location) = &self.location {
+            out.push_str(&format!("\nLocation: {location}"));
+        }
+        out
+    }
+}
+
 pub struct InfoGatherer {
+    pub current_event: Option<CurrentEvent>,
     pub next_event_at: Option<EventInfo>,
+    format: Format,
+    /// Ask the running daemon instead, if there is one
+    live: bool,
 }
 impl InfoGatherer {
     pub fn print(self, context: Context) -> crate::error::Result<()> {
-        let info = Info {
-            next_event: self.next_event_at,
-            configuration: &context.config,
+        let live = if self.live && client::daemon_running() {
+            query_daemon()
+        } else {
+            None
+        };
+        let mut configuration = Configuration::clone(&context.config);
+        let info = match live {
+            Some(live) => {
+                configuration.enabled = live.enabled;
+                Info {
+                    current_event: live.current_event,
+                    next_event: live.next_event.map(|event| NextEvent {
+                        event,
+                        now: live.now,
+                    }),
+                    daemon: true,
+                    sources: Configuration::sources(&context.config_path)?,
+                    configuration: &configuration,
+                }
+            }
+            None => Info {
+                current_event: self.current_event,
+                next_event: self.next_event_at.map(|event| NextEvent {
+                    event,
+                    now: Utc::now(),
+                }),
+                daemon: false,
+                sources: Configuration::sources(&context.config_path)?,
+                configuration: &configuration,
+            },
         };
-        println!("{info}");
+        match self.format {
+            Format::Text => println!("{}", info.render()?),
+            Format::Json => println!("{}", serde_json::to_string_pretty(&info)?),
+            Format::Waybar => println!("{}", serde_json::to_string(&info.waybar())?),
+        }
         Ok(())
     }
 
-    pub fn new(next_event_at: Option<EventInfo>) -> Self {
-        Self { next_event_at }
+    pub fn new(
+        current_event: Option<CurrentEvent>,
+        next_event_at: Option<EventInfo>,
+        format: Format,
+        live: bool,
+    ) -> Self {
+        Self {
+            current_event,
+            next_event_at,
+            format,
+            live,
+        }
     }
 }
 
-impl<'a> Display for Info<'a> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        if let Some(ev) = &self.next_event {
-            f.write_str("Event info: ")?;
-            ev.fmt(f)?;
-            f.write_str("\n")?;
+/// The live info of the running daemon, `None` with a warning if it can't answer
+fn query_daemon() -> Option<DaemonInfo> {
+    match client::send(Action::QueryInfo) {
+        Ok(Response::Info(info)) => Some(info),
+        Ok(response) => {
+            log::warn!("Unexpected answer to the info query, showing the config file: {response}");
+            None
+        }
+        Err(err) => {
+            log::warn!("Failed to query the daemon, showing the config file: {err}");
+            None
+        }
+    }
+}
+
+impl<'a> Info<'a> {
+    /// Human readable form, fails with the actual cause if the configuration can't be serialized
+    pub fn render(&self) -> crate::error::Result<String> {
+        let mut out = String::new();
+        if let Some(current) = &self.current_event {
+            out.push_str(&format!("Current: {current}\n"));
+        }
+        match &self.next_event {
+            Some(ev) => out.push_str(&format!("Next event:\n{ev}\n")),
+            None => out.push_str("No pending event\n"),
+        }
+        if self.daemon {
+            out.push_str(&format!(
+                "Daemon: running, {}\n",
+                if self.configuration.enabled {
+                    "enabled"
+                } else {
+                    "disabled"
+                }
+            ));
+        }
+        if self.sources.found {
+            out.push_str(&format!("Config file: {}\n", self.sources.path));
         } else {
-            f.write_str("No pending event\n")?;
+            out.push_str(&format!(
+                "Config file: {} (not found, using defaults)\n",
+                self.sources.path
+            ));
         }
-        self.configuration.fmt(f)?;
-        Ok(())
+        for (origin, label) in [
+            (Origin::File, "Set in file"),
+            (Origin::Environment, "Set in environment"),
+        ] {
+            let fields = self.sources.fields_from(origin);
+            if !fields.is_empty() {
+                out.push_str(&format!("{label}: {}\n", fields.join(", ")));
+            }
+        }
+        if self.configuration.actions.is_empty() {
+            out.push_str("No actions configured, events fire without running anything\n");
+        }
+        out.push_str(&self.configuration.to_toml()?);
+        Ok(out)
+    }
+
+    /// The next event and the time until it
+    pub fn waybar(&self) -> WaybarStatus {
+        let class = if self.configuration.enabled {
+            "enabled"
+        } else {
+            "disabled"
+        };
+        match &self.next_event {
+            Some(ev) => WaybarStatus {
+                text: format!(
+                    "{} {} {}",
+                    icon(ev.event.trigger),
+                    ev.event.trigger,
+                    ev.event.countdown(ev.now)
+                ),
+                tooltip: ev.to_string(),
+                class,
+            },
+            None => WaybarStatus {
+                text: "No pending event".to_string(),
+                tooltip: "No pending event".to_string(),
+                class,
+            },
+        }
+    }
+}
+
+fn icon(trigger: ActionTrigger) -> &'static str {
+    match trigger {
+        ActionTrigger::Dawn => "🌄",
+        ActionTrigger::Sunrise => "🌅",
+        ActionTrigger::Sunset => "🌇",
+        ActionTrigger::Dusk => "🌃",
+        ActionTrigger::SolarNoon => "☀️",
+        ActionTrigger::SolarMidnight => "🌙",
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{DateTime, FixedOffset, TimeDelta};
+
+    use crate::{
+        config::{ConfigSources, Configuration},
+        scheduler::{ActionTrigger, Polar},
+    };
+
+    use super::{EventInfo, Info, NextEvent};
+
+    #[test]
+    fn info_serializes_to_json() {
+        let configuration = Configuration::default();
+        let now = DateTime::from_timestamp(1752414761, 0).unwrap();
+        let info = Info {
+            current_event: None,
+            next_event: Some(NextEvent {
+                event: EventInfo {
+                    at: now + TimeDelta::minutes(90),
+                    trigger: ActionTrigger::Sunset,
+                    action: Some("true".to_string()),
+                    location: None,
+                    polar: None,
+                },
+                now,
+            }),
+            daemon: false,
+            sources: ConfigSources::default(),
+            configuration: &configuration,
+        };
+
+        let json: serde_json::Value = serde_json::to_value(&info).unwrap();
+
+        assert_eq!(json["next_event"]["at"], "2025-07-13T15:22:41Z");
+        assert_eq!(json["next_event"]["trigger"], "sunset");
+        assert_eq!(json["next_event"]["action"], "true");
+        assert_eq!(json["next_event"]["seconds_until"], 90 * 60);
+        assert_eq!(json["configuration"]["enabled"], true);
+    }
+
+    #[test]
+    fn event_renders_one_detail_per_line() {
+        let at = DateTime::from_timestamp(1752414761, 0).unwrap();
+        let berlin = FixedOffset::east_opt(2 * 3600).unwrap();
+        let mut event = EventInfo {
+            at,
+            trigger: ActionTrigger::Sunset,
+            action: None,
+            location: None,
+            polar: None,
+        };
+
+        assert_eq!(
+            event.describe(at - TimeDelta::minutes(90), &berlin),
+            "At: 2025-07-13 15:52:41 +02:00 (in 1h30m)\nTrigger: sunset"
+        );
+        event.action = Some("hyprctl hyprsunset temperature 4500".to_string());
+        event.location = Some("Berlin".to_string());
+        event.polar = Some(Polar::Day);
+        assert_eq!(
+            event.describe(at, &berlin),
+            concat!(
+                "At: 2025-07-13 15:52:41 +02:00 (now)\n",
+                "Trigger: sunset (polar day, the sun doesn't set today)\n",
+                "Action: hyprctl hyprsunset temperature 4500\n",
+                "Location: Berlin"
+            )
+        );
+    }
+
+    #[test]
+    fn countdown_handles_passed_events() {
+        let at = DateTime::from_timestamp(1752414761, 0).unwrap();
+        let event = EventInfo {
+            at,
+            trigger: ActionTrigger::Sunset,
+            action: None,
+            location: None,
+            polar: None,
+        };
+
+        assert_eq!(
+            event.time_until(at - TimeDelta::minutes(125)),
+            TimeDelta::minutes(125)
+        );
+        assert_eq!(event.countdown(at - TimeDelta::minutes(125)), "in 2h5m");
+        assert_eq!(event.countdown(at), "now");
+        assert_eq!(
+            event.time_until(at + TimeDelta::seconds(3)),
+            TimeDelta::seconds(-3)
+        );
+        assert_eq!(event.countdown(at + TimeDelta::seconds(3)), "now");
+    }
+
+    #[test]
+    fn waybar_shows_time_until_next_event() {
+        let configuration = Configuration {
+            enabled: false,
+            ..Configuration::default()
+        };
+        let at = DateTime::from_timestamp(1752414761, 0).unwrap();
+        let info = Info {
+            current_event: None,
+            next_event: Some(NextEvent {
+                event: EventInfo {
+                    at,
+                    trigger: ActionTrigger::Sunrise,
+                    action: None,
+                    location: None,
+                    polar: None,
+                },
+                now: at - TimeDelta::minutes(133),
+            }),
+            daemon: false,
+            sources: ConfigSources::default(),
+            configuration: &configuration,
+        };
+
+        let status = info.waybar();
+
+        assert_eq!(status.text, "🌅 sunrise in 2h13m");
+        assert_eq!(status.class, "disabled");
+        assert!(status.tooltip.contains("sunrise"));
     }
 }
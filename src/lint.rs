@@ -0,0 +1,184 @@
+use std::fmt::Display;
+
+use crate::{config::Actions, context::Context};
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Finding {
+    pub field: String,
+    pub reason: &'static str,
+}
+
+impl Display for Finding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&format!("{}: {}", self.field, self.reason))
+    }
+}
+
+/// Heuristic check of the configured commands for obviously dangerous patterns.
+///
+/// This is advisory only, it can neither catch everything nor prove a command safe.
+pub fn lint_actions(actions: &Actions) -> Vec<Finding> {
+    let mut findings = vec![];
+    for (field, command) in actions.commands() {
+        for reason in lint_command(command) {
+            findings.push(Finding {
+                field: field.clone(),
+                reason,
+            });
+        }
+    }
+    findings
+}
+
+fn lint_command(command: &str) -> Vec<&'static str> {
+    let mut reasons = vec![];
+    let words: Vec<&str> = command.split_whitespace().collect();
+    if is_recursive_root_removal(command) {
+        reasons.push("recursively removes the root or home directory");
+    }
+    if words.iter().any(|w| w.starts_with("mkfs")) {
+        reasons.push("creates a file system");
+    }
+    if words.iter().any(|w| w.starts_with("of=/dev/")) || command.contains("> /dev/sd") {
+        reasons.push("writes to a block device");
+    }
+    if command.contains(":(){") {
+        reasons.push("looks like a fork bomb");
+    }
+    if pipes_into_shell(&words) {
+        reasons.push("pipes downloaded content into a shell");
+    }
+    if has_unquoted_expansion(command) {
+        reasons.push("contains an unquoted variable expansion");
+    }
+    reasons
+}
+
+/// `-r` or `-R` on its own or among other short options, or `--recursive`
+fn is_recursive_flag(word: &str) -> bool {
+    word == "--recursive"
+        || word.strip_prefix('-').is_some_and(|flags| {
+            !flags.starts_with('-') && (flags.contains('r') || flags.contains('R'))
+        })
+}
+
+/// Whether any of the commands chained in `command` is a recursive `rm` of `/` or home
+fn is_recursive_root_removal(command: &str) -> bool {
+    command.split([';', '&', '|']).any(|part| {
+        let mut words = part
+            .split_whitespace()
+            .skip_while(|w| matches!(*w, "sudo" | "doas" | "exec"));
+        let Some(program) = words.next() else {
+            return false;
+        };
+        let args = words.collect::<Vec<_>>();
+        (program == "rm" || program.ends_with("/rm"))
+            && args.iter().any(|w| is_recursive_flag(w))
+            && args
+                .iter()
+                .any(|w| matches!(*w, "/" | "/*" | "~" | "~/" | "~/*" | "$HOME" | "\"$HOME\""))
+    })
+}
+
+fn pipes_into_shell(words: &[&str]) -> bool {
+    let downloads = words.iter().any(|w| matches!(*w, "curl" | "wget"));
+    let into_shell = words
+        .windows(2)
+        .any(|w| w[0] == "|" && matches!(w[1], "sh" | "bash" | "zsh" | "sudo"));
+    downloads && into_shell
+}
+
+fn has_unquoted_expansion(command: &str) -> bool {
+    let mut single = false;
+    let mut double = false;
+    let mut chars = command.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if !single => {
+                chars.next();
+            }
+            '\'' if !double => single = !single,
+            '"' if !single => double = !double,
+            '$' if !single
+                && !double
+                && chars
+                    .peek()
+                    .is_some_and(|n| n.is_ascii_alphabetic() || *n == '_' || *n == '{') =>
+            {
+                return true;
+            }
+            _ => {}
+        }
+    }
+    false
+}
+
+pub struct Linter {
+    strict: bool,
+}
+
+impl Linter {
+    pub fn new(strict: bool) -> Self {
+        Self { strict }
+    }
+
+    pub fn run(self, context: Context) -> crate::error::Result<()> {
+        let findings = lint_actions(&context.config.actions);
+        if findings.is_empty() {
+            println!("No suspicious actions found");
+            return Ok(());
+        }
+        for finding in &findings {
+            println!("warning: {finding}");
+        }
+        if self.strict {
+            Err(crate::error::Error::InvalidAction(format!(
+                "{} suspicious action(s) found",
+                findings.len()
+            )))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::lint_command;
+
+    #[test]
+    fn flags_root_removal() {
+        assert!(!lint_command("rm -rf /").is_empty());
+        assert!(!lint_command("sudo rm -fr ~").is_empty());
+        assert!(!lint_command("rm -fr ~").is_empty());
+        assert!(!lint_command("cd /tmp && rm --recursive /").is_empty());
+    }
+
+    #[test]
+    fn removal_needs_a_recursive_rm_command() {
+        assert!(lint_command("rm --force ~").is_empty());
+        assert!(lint_command("rm --preserve-root -f /").is_empty());
+        assert!(lint_command("echo rm -rf ~").is_empty());
+        assert!(lint_command("rm -f /tmp/x; ls -R ~").is_empty());
+    }
+
+    #[test]
+    fn flags_unquoted_expansion() {
+        assert_eq!(
+            lint_command("swww img $WALLPAPER"),
+            vec!["contains an unquoted variable expansion"]
+        );
+    }
+
+    #[test]
+    fn accepts_quoted_expansion() {
+        assert!(lint_command("swww img \"$WALLPAPER\"").is_empty());
+        assert!(lint_command("echo '$NOT_EXPANDED'").is_empty());
+    }
+
+    #[test]
+    fn accepts_common_commands() {
+        assert!(lint_command("hyprctl hyprsunset temperature 4500").is_empty());
+        assert!(lint_command("rm -f /tmp/night-mode").is_empty());
+    }
+}
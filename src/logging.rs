@@ -0,0 +1,106 @@
+use std::{
+    fmt::Display,
+    io::{IsTerminal, Write},
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+use env_logger::{
+    Env,
+    fmt::{
+        WriteStyle,
+        style::{AnsiColor, Style},
+    },
+};
+
+use crate::scheduler::ActionTrigger;
+
+static COLORED: AtomicBool = AtomicBool::new(false);
+
+const LEVELS: [&str; 4] = ["warn", "info", "debug", "trace"];
+
+/// Sets up the logger, colored and more verbose when run interactively.
+///
+/// Each `verbose` step raises the level by one. `RUST_LOG` overrides the level.
+pub fn init(foreground: bool, verbose: u8) {
+    let colored = foreground || std::io::stderr().is_terminal();
+    COLORED.store(colored, Ordering::Relaxed);
+
+    let mut builder = env_logger::Builder::from_env(
+        Env::default().default_filter_or(default_level(colored, verbose)),
+    );
+    if colored {
+        builder
+            .write_style(WriteStyle::Always)
+            .format(|buf, record| {
+                let level = buf.default_level_style(record.level());
+                let dimmed = Style::new().dimmed();
+                writeln!(
+                    buf,
+                    "{dimmed}{}{dimmed:#} {level}{:<5}{level:#} {}",
+                    buf.timestamp_seconds(),
+                    record.level(),
+                    record.args()
+                )
+            });
+    } else {
+        builder.write_style(WriteStyle::Never);
+    }
+    builder.init();
+}
+
+/// Warnings only unless run interactively or asked for more
+fn default_level(colored: bool, verbose: u8) -> &'static str {
+    let base = usize::from(colored);
+    LEVELS[(base + usize::from(verbose)).min(LEVELS.len() - 1)]
+}
+
+/// Renders `trigger` for log messages, colored if the log output is a terminal or
+/// `--foreground-log` is set
+pub fn paint(trigger: ActionTrigger) -> impl Display {
+    struct Painted(ActionTrigger);
+
+    impl Display for Painted {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            if !COLORED.load(Ordering::Relaxed) {
+                return self.0.fmt(f);
+            }
+            let color = match self.0 {
+                ActionTrigger::Dawn => AnsiColor::Cyan,
+                ActionTrigger::Sunrise => AnsiColor::Yellow,
+                ActionTrigger::Sunset => AnsiColor::Red,
+                ActionTrigger::Dusk => AnsiColor::Blue,
+                ActionTrigger::SolarNoon => AnsiColor::BrightYellow,
+                ActionTrigger::SolarMidnight => AnsiColor::Magenta,
+            };
+            let style = Style::new().fg_color(Some(color.into())).bold();
+            write!(f, "{style}{}{style:#}", self.0)
+        }
+    }
+
+    Painted(trigger)
+}
+
+/// Renders `duration` compactly for log messages, e.g. `2h11m` or `42s`
+pub fn duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    match (secs / 3600, secs / 60 % 60, secs % 60) {
+        (0, 0, s) => format!("{s}s"),
+        (0, m, s) => format!("{m}m{s}s"),
+        (h, m, _) => format!("{h}h{m}m"),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::default_level;
+
+    #[test]
+    fn verbose_raises_level_from_default() {
+        assert_eq!(default_level(false, 0), "warn");
+        assert_eq!(default_level(true, 0), "info");
+        assert_eq!(default_level(false, 2), "debug");
+        assert_eq!(default_level(true, 2), "trace");
+        assert_eq!(default_level(false, 9), "trace");
+    }
+}
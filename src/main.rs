@@ -1,37 +1,197 @@
+use std::process::ExitCode;
+
+use chrono::NaiveDate;
 use clap::{Parser, Subcommand};
 use config::Configuration;
 use context::Context;
+use info::Format;
+use scheduler::ActionTrigger;
 
 pub mod actions;
+pub mod audit;
+pub mod bench;
+pub mod cities;
+pub mod client;
 pub mod config;
+pub mod confirm;
 pub mod context;
 pub mod daemon;
+pub mod date;
+pub mod desktop;
+pub mod doctor;
+pub mod dump_schedule;
 pub mod error;
+pub mod geoclue;
 pub mod info;
+pub mod lint;
+pub mod logging;
+pub mod metrics;
+pub mod outputs;
+pub mod pid_file;
+pub mod precompute;
+pub mod runner;
 pub mod scheduler;
 pub mod state;
+pub mod systemd;
+pub mod test_action;
+pub mod upcoming;
+pub mod validate;
 
 #[derive(Parser, Clone)]
 #[command(version, about)]
 pub struct Args {
     #[command(subcommand)]
     command: Option<Commands>,
-    #[arg(short,long, default_value = Configuration::DEFAULT_PATH)]
-    config: String,
+    /// Config file, `~/.config/hyprsunrisewatcher/config.toml` if not given. Unlike the default,
+    /// a file given here has to exist
+    #[arg(short, long)]
+    config: Option<String>,
+    /// Show the events of another day: `tomorrow`, `yesterday`, `+Nd`, `-Nd` or `YYYY-MM-DD`
+    #[arg(long, value_parser = date::parse_date_arg, allow_hyphen_values = true)]
+    date: Option<NaiveDate>,
+    /// Colored, human friendly logs even if stderr is not a terminal
+    #[arg(long, global = true)]
+    foreground_log: bool,
+    /// Log more, `-v` for info, `-vv` for debug and `-vvv` for trace messages
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
+    verbose: u8,
+    /// Don't ask before overwriting files, needed to overwrite them without a terminal
+    #[arg(short, long, global = true)]
+    yes: bool,
+    /// Print the result of `info` and `upcoming` as text or JSON, `waybar` applies to `info`
+    #[arg(long, global = true, value_enum, default_value_t)]
+    format: Format,
 }
 
 #[derive(Subcommand, Clone, PartialEq, Debug)]
 pub enum Commands {
-    Start,
-    PrintDefaultConfig,
+    Start {
+        /// Run the actions of the current event and exit, e.g. from a systemd timer
+        #[arg(long)]
+        once: bool,
+    },
+    /// Stop the running daemon
+    Stop,
+    /// Let the running daemon run actions again
+    Enable,
+    /// Keep the running daemon from running actions until it is enabled again
+    Disable,
+    /// Enable the running daemon if it is disabled, disable it otherwise
+    Toggle,
+    /// Show the next event, the same as running without a command
+    Info,
+    PrintDefaultConfig {
+        /// Write the config to this file instead of stdout
+        #[arg(long)]
+        out: Option<String>,
+    },
+    /// Write the default config to the config file, creating its directory
+    Init {
+        /// Replace an existing config file
+        #[arg(long)]
+        force: bool,
+    },
+    /// Switch the running daemon to a location of `[automatic.profiles]` until it reloads, back to
+    /// the `[automatic]` one without a name
+    Profile { name: Option<String> },
+    /// Make the running daemon reload its config file
+    Reload {
+        /// Only validate the config file, keep the running configuration
+        #[arg(long)]
+        check: bool,
+    },
+    /// Exit successfully if a daemon is running
+    IsRunning,
+    /// Print whether the daemon is running, exits with 3 if it is stopped
+    Status,
+    /// Exit successfully if it is dark now, see `day_boundary` and `night_boundary`
+    IsDark,
+    /// Compute a year of solar events for the configured location ahead of time
+    Precompute {
+        #[arg(long)]
+        year: i32,
+        #[arg(long)]
+        out: String,
+    },
+    /// Check the configuration for problems, e.g. events that don't happen at the location
+    Doctor,
+    /// Load the config file the way the daemon does and report whether it is valid
+    Validate {
+        /// Fail on warnings too, e.g. suspicious actions
+        #[arg(long)]
+        strict: bool,
+    },
+    /// Check the configured actions for dangerous commands
+    Lint {
+        /// Fail if anything suspicious is found
+        #[arg(long)]
+        strict: bool,
+    },
+    /// List the next events with the time remaining until each
+    #[command(visible_alias = "next")]
+    Upcoming {
+        /// Number of events, at most 100
+        #[arg(short = 'n', long, default_value_t = 3)]
+        count: usize,
+        /// Print the events as JSON, e.g. for status bars
+        #[arg(long)]
+        json: bool,
+        /// List the events from this day on, see the top level `--date`
+        #[arg(long, value_parser = date::parse_date_arg, allow_hyphen_values = true)]
+        date: Option<NaiveDate>,
+    },
+    /// Print the dawn, sunrise, sunset and dusk of a day at the configured location, to check the
+    /// coordinates
+    DumpSchedule {
+        /// The day to print, today by default, see the top level `--date`
+        #[arg(long, value_parser = date::parse_date_arg, allow_hyphen_values = true)]
+        date: Option<NaiveDate>,
+    },
+    /// Run the commands of an event right away, e.g. to debug them without waiting for sunset
+    TestAction {
+        /// The event whose commands are run, e.g. `sunset`
+        trigger: ActionTrigger,
+        /// Only print the commands
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Measure how long the scheduler takes to compute the next event
+    #[command(hide = true)]
+    Bench {
+        #[arg(short = 'n', long, default_value_t = 10_000)]
+        iterations: usize,
+    },
+}
+
+impl Commands {
+    /// Whether the command works with the config file, rather than e.g. generating one
+    fn reads_config(&self) -> bool {
+        // `validate` loads the file itself to report the errors
+        !matches!(
+            self,
+            Commands::PrintDefaultConfig { .. } | Commands::Init { .. } | Commands::Validate { .. }
+        )
+    }
 }
 
-fn main() -> crate::error::Result<()> {
+fn main() -> Result<ExitCode, Box<dyn std::error::Error>> {
     let args = Args::parse();
-    let shell_expaned = shellexpand::full(&args.config)?;
-    let config = Configuration::load(&shell_expaned)?;
-    let context = Context::create_from_config(config, shell_expaned.into_owned());
-    context.run(args)
+    logging::init(args.foreground_log, args.verbose);
+    let config_path = config::expand_path(
+        "config",
+        args.config
+            .as_deref()
+            .unwrap_or(Configuration::DEFAULT_PATH),
+    )?;
+    let config = match &args.command {
+        // the file may not exist yet, or be broken and about to be replaced
+        Some(command) if !command.reads_config() => Configuration::default(),
+        // a typo in an explicit path shouldn't silently run with the defaults
+        _ if args.config.is_some() => Configuration::load_existing(&config_path)?,
+        _ => Configuration::load(&config_path)?,
+    };
+    let context = Context::create_from_config(config, config_path);
+    Ok(context.run(args)?)
 }
 
 #[cfg(test)]
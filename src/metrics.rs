@@ -0,0 +1,124 @@
+use std::{fmt::Write as _, path::Path};
+
+use chrono::Utc;
+
+use crate::{
+    config::Configuration,
+    info::EventInfo,
+    scheduler::{EventSource, TriggerSource},
+};
+
+/// Counters exported to the Prometheus textfile collector
+#[derive(Debug, Default)]
+pub struct Metrics {
+    pub triggers_total: u64,
+    pub last_action_exit_code: Option<i32>,
+}
+
+impl Metrics {
+    pub fn render(&self, enabled: bool, next_event: Option<&EventInfo>) -> String {
+        let mut out = String::new();
+        metric(
+            &mut out,
+            "hsw_triggers_total",
+            "counter",
+            "Actions triggered since the daemon started.",
+            self.triggers_total,
+        );
+        metric(
+            &mut out,
+            "hsw_enabled",
+            "gauge",
+            "Whether the daemon fires actions.",
+            enabled as u8,
+        );
+        if let Some(event) = next_event {
+            metric(
+                &mut out,
+                "hsw_next_event_timestamp",
+                "gauge",
+                "Unix time of the next scheduled event.",
+                event.at.timestamp(),
+            );
+        }
+        if let Some(code) = self.last_action_exit_code {
+            metric(
+                &mut out,
+                "hsw_last_action_exit_code",
+                "gauge",
+                "Exit code of the last waited for action.",
+                code,
+            );
+        }
+        out
+    }
+
+    /// Atomically replaces the textfile at `path` with the current values
+    pub fn write(&self, path: &str, config: &Configuration) -> crate::error::Result<()> {
+        let next_event = TriggerSource::from_config(config)?.next_event_at(Utc::now());
+        let content = self.render(config.enabled, next_event.as_ref());
+        let tmp = Path::new(path).with_extension("prom.tmp");
+        std::fs::write(&tmp, content)?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+}
+
+fn metric(out: &mut String, name: &str, kind: &str, help: &str, value: impl std::fmt::Display) {
+    // writing into a String can't fail
+    let _ = writeln!(
+        out,
+        "# HELP {name} {help}\n# TYPE {name} {kind}\n{name} {value}"
+    );
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::DateTime;
+
+    use crate::{info::EventInfo, scheduler::ActionTrigger};
+
+    use super::Metrics;
+
+    #[test]
+    fn render_all_metrics() {
+        let metrics = Metrics {
+            triggers_total: 3,
+            last_action_exit_code: Some(1),
+        };
+        let event = EventInfo {
+            at: DateTime::from_timestamp(1752414761, 0).unwrap(),
+            trigger: ActionTrigger::Sunset,
+            action: None,
+            location: None,
+            polar: None,
+        };
+
+        let rendered = metrics.render(true, Some(&event));
+
+        assert_eq!(
+            rendered,
+            "# HELP hsw_triggers_total Actions triggered since the daemon started.\n\
+             # TYPE hsw_triggers_total counter\n\
+             hsw_triggers_total 3\n\
+             # HELP hsw_enabled Whether the daemon fires actions.\n\
+             # TYPE hsw_enabled gauge\n\
+             hsw_enabled 1\n\
+             # HELP hsw_next_event_timestamp Unix time of the next scheduled event.\n\
+             # TYPE hsw_next_event_timestamp gauge\n\
+             hsw_next_event_timestamp 1752414761\n\
+             # HELP hsw_last_action_exit_code Exit code of the last waited for action.\n\
+             # TYPE hsw_last_action_exit_code gauge\n\
+             hsw_last_action_exit_code 1\n"
+        );
+    }
+
+    #[test]
+    fn render_skips_unknown_values() {
+        let rendered = Metrics::default().render(false, None);
+
+        assert!(rendered.contains("hsw_enabled 0\n"));
+        assert!(!rendered.contains("hsw_next_event_timestamp"));
+        assert!(!rendered.contains("hsw_last_action_exit_code"));
+    }
+}
@@ -0,0 +1,51 @@
+use std::{io, process::Command};
+
+use serde::Deserialize;
+
+use crate::config::Configuration;
+
+#[derive(Deserialize)]
+struct Monitor {
+    name: String,
+}
+
+/// Names of the outputs actions apply to: the configured ones, or every monitor Hyprland reports
+pub fn outputs(config: &Configuration) -> Vec<String> {
+    if !config.outputs.is_empty() {
+        return config.outputs.clone();
+    }
+    match hyprland_outputs() {
+        Ok(outputs) => outputs,
+        Err(err) => {
+            log::debug!("Failed to enumerate outputs through hyprctl: {err}");
+            vec![]
+        }
+    }
+}
+
+fn hyprland_outputs() -> crate::error::Result<Vec<String>> {
+    let output = Command::new("hyprctl").args(["monitors", "-j"]).output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!("hyprctl exited with {}", output.status)).into());
+    }
+    parse_monitors(&output.stdout)
+}
+
+fn parse_monitors(json: &[u8]) -> crate::error::Result<Vec<String>> {
+    let monitors: Vec<Monitor> = serde_json::from_slice(json)?;
+    Ok(monitors.into_iter().map(|m| m.name).collect())
+}
+
+#[cfg(test)]
+mod test {
+    use super::parse_monitors;
+
+    #[test]
+    fn parses_hyprctl_monitors() {
+        let json = br#"[{"id": 0, "name": "DP-1", "width": 2560}, {"id": 1, "name": "HDMI-A-1"}]"#;
+
+        let outputs = parse_monitors(json).unwrap();
+
+        assert_eq!(outputs, ["DP-1", "HDMI-A-1"]);
+    }
+}
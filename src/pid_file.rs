@@ -0,0 +1,53 @@
+use std::fs;
+
+/// The PID of the running daemon, removed again when the daemon exits
+#[derive(Debug)]
+pub struct PidFile {
+    path: String,
+}
+
+impl PidFile {
+    /// Writes the PID of this process to `path`.
+    ///
+    /// Only called once the daemon socket is bound, so an existing file was left behind by a
+    /// daemon that died and is overwritten.
+    pub fn create(path: &str) -> crate::error::Result<Self> {
+        fs::write(path, format!("{}\n", std::process::id()))?;
+        Ok(Self {
+            path: path.to_string(),
+        })
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        if let Err(err) = fs::remove_file(&self.path) {
+            log::warn!("Failed to remove PID file {}: {err}", self.path);
+        }
+    }
+}
+
+/// The PID written to `path`, `None` if there is no readable PID file
+pub fn read(path: &str) -> Option<u32> {
+    fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{PidFile, read};
+
+    #[test]
+    fn pid_file_lives_as_long_as_the_daemon() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("hyprsunrisewatcher.pid");
+        let path = path.to_str().unwrap();
+        std::fs::write(path, "not a pid").unwrap();
+
+        let pid_file = PidFile::create(path).unwrap();
+
+        assert_eq!(read(path), Some(std::process::id()));
+        drop(pid_file);
+        assert_eq!(read(path), None);
+        assert!(!std::path::Path::new(path).exists());
+    }
+}
@@ -0,0 +1,133 @@
+use std::{collections::BTreeMap, fs::File, io::BufWriter};
+
+use chrono::{Datelike, NaiveDate};
+use serde::{Deserialize, Serialize};
+use sunrise::Coordinates;
+
+use crate::{config::Twilight, confirm, context::Context, scheduler::SolarTimes};
+
+/// Solar events of a whole year, computed ahead of time for a fixed location
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PrecomputedEvents {
+    pub latitude: f64,
+    pub longitude: f64,
+    /// Missing in caches written before twilight was configurable, those are civil
+    #[serde(default)]
+    pub twilight: Twilight,
+    pub days: BTreeMap<NaiveDate, SolarTimes>,
+}
+
+impl PrecomputedEvents {
+    pub fn compute(
+        latitude: f64,
+        longitude: f64,
+        twilight: Twilight,
+        year: i32,
+    ) -> crate::error::Result<Self> {
+        let coords = Coordinates::new(latitude, longitude)
+            .ok_or(crate::error::Error::InvalidCoordinates(latitude, longitude))?;
+        let first = NaiveDate::from_yo_opt(year, 1).ok_or_else(|| {
+            crate::error::Error::InvalidEventCache(format!("year {year} is out of range"))
+        })?;
+        let days = first
+            .iter_days()
+            .take_while(|d| d.year() == year)
+            .map(|d| (d, SolarTimes::compute(coords, d, twilight)))
+            .collect();
+        Ok(Self {
+            latitude,
+            longitude,
+            twilight,
+            days,
+        })
+    }
+
+    pub fn load(path: &str) -> crate::error::Result<Self> {
+        let file = File::open(path).map_err(|err| {
+            crate::error::Error::InvalidEventCache(format!("failed to open {path}: {err}"))
+        })?;
+        Ok(serde_json::from_reader(std::io::BufReader::new(file))?)
+    }
+
+    pub fn get(&self, date: NaiveDate) -> Option<SolarTimes> {
+        self.days.get(&date).copied()
+    }
+
+    pub fn matches(&self, latitude: f64, longitude: f64, twilight: Twilight) -> bool {
+        (self.latitude - latitude).abs() < 1e-6
+            && (self.longitude - longitude).abs() < 1e-6
+            && self.twilight == twilight
+    }
+}
+
+pub struct Precomputer {
+    year: i32,
+    out: String,
+    yes: bool,
+}
+
+impl Precomputer {
+    pub fn new(year: i32, out: String, yes: bool) -> Self {
+        Self { year, out, yes }
+    }
+
+    pub fn run(self, context: Context) -> crate::error::Result<()> {
+        let Some(auto) = &context.config.automatic else {
+            return Err(crate::error::Error::InvalidEventCache(
+                "precomputing requires an [automatic] location".to_string(),
+            ));
+        };
+        let (latitude, longitude) = auto.coordinates()?;
+        let events = PrecomputedEvents::compute(latitude, longitude, auto.twilight, self.year)?;
+        confirm::overwrite(&self.out, self.yes)?;
+        let writer = BufWriter::new(File::create(&self.out)?);
+        serde_json::to_writer_pretty(writer, &events)?;
+        println!(
+            "Wrote {} days of events for {} to {}",
+            events.days.len(),
+            self.year,
+            self.out
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::NaiveDate;
+
+    use crate::config::Twilight;
+
+    use super::PrecomputedEvents;
+
+    #[test]
+    fn compute_covers_whole_year() {
+        let events =
+            PrecomputedEvents::compute(49.598121, 11.003653, Twilight::Civil, 2024).unwrap();
+
+        assert_eq!(events.days.len(), 366);
+        assert!(
+            events
+                .get(NaiveDate::from_ymd_opt(2024, 12, 31).unwrap())
+                .is_some()
+        );
+        assert!(
+            events
+                .get(NaiveDate::from_ymd_opt(2025, 1, 1).unwrap())
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn round_trips_through_json() {
+        let events =
+            PrecomputedEvents::compute(49.598121, 11.003653, Twilight::Nautical, 2025).unwrap();
+
+        let json = serde_json::to_string(&events).unwrap();
+        let parsed: PrecomputedEvents = serde_json::from_str(&json).unwrap();
+
+        assert!(parsed.matches(49.598121, 11.003653, Twilight::Nautical));
+        assert!(!parsed.matches(49.598121, 11.003653, Twilight::Civil));
+        assert_eq!(parsed.days, events.days);
+    }
+}
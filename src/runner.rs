@@ -0,0 +1,658 @@
+#[cfg(unix)]
+use std::os::unix::process::CommandExt;
+use std::{
+    io::{self, BufRead, BufReader, Read},
+    process::{Child, Command, ExitStatus, Stdio},
+    sync::{Arc, Condvar, Mutex, mpsc::Sender},
+    thread::{JoinHandle, sleep},
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    actions::{Action, Request},
+    config::Configuration,
+    error::Error,
+    info::EventInfo,
+    logging,
+    scheduler::ActionTrigger,
+};
+
+const OUTPUT_PLACEHOLDER: &str = "{output}";
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Runs `action` through the configured `shell`
+pub fn command(config: &Configuration, action: &str) -> Command {
+    let mut command = Command::new(&config.shell);
+    command.args(shell_args(config)).arg(action);
+    command
+}
+
+fn shell_args(config: &Configuration) -> Vec<&str> {
+    match &config.shell_args {
+        Some(args) => args.iter().map(String::as_str).collect(),
+        None => vec![shell_flag(config)],
+    }
+}
+
+fn shell_flag(config: &Configuration) -> &'static str {
+    if cfg!(windows) {
+        "/C"
+    } else if config.login_shell {
+        "-lc"
+    } else {
+        "-c"
+    }
+}
+
+/// Like `command`, but `action` is started in the background by a short lived shell in its own
+/// process group. It is reparented to init right away and so outlives the daemon.
+#[cfg(not(windows))]
+pub fn detached(config: &Configuration, action: &str) -> Command {
+    let mut command = Command::new("sh");
+    command
+        .arg("-c")
+        .arg("\"$@\" &")
+        .arg("sh")
+        .arg(&config.shell)
+        .args(shell_args(config))
+        .arg(action);
+    #[cfg(unix)]
+    command.process_group(0);
+    command
+}
+
+/// Like `command`, but `action` is started in the background by `start`, which returns right
+/// away and so lets the action outlive the daemon.
+#[cfg(windows)]
+pub fn detached(config: &Configuration, action: &str) -> Command {
+    use std::os::windows::process::CommandExt;
+
+    let mut command = Command::new("cmd");
+    // the empty title keeps `start` from taking the quoted shell for the window title
+    command
+        .args(["/C", "start"])
+        .raw_arg("\"\"")
+        .arg("/B")
+        .arg(&config.shell)
+        .args(shell_args(config))
+        .arg(action);
+    command
+}
+
+/// The commands to run for `action`, one per output if it contains the `{output}` placeholder
+pub fn commands(
+    config: &Configuration,
+    action: &str,
+    outputs: &[String],
+    detach: bool,
+) -> Vec<Command> {
+    let spawnable = |action: &str| {
+        if detach {
+            detached(config, action)
+        } else {
+            command(config, action)
+        }
+    };
+    let mut commands = if action.contains(OUTPUT_PLACEHOLDER) {
+        outputs
+            .iter()
+            .map(|output| {
+                let mut command = spawnable(&action.replace(OUTPUT_PLACEHOLDER, output));
+                command.env("HSW_OUTPUT", output);
+                command
+            })
+            .collect()
+    } else {
+        vec![spawnable(action)]
+    };
+    for command in &mut commands {
+        command.env("HSW_OUTPUTS", outputs.join(" "));
+    }
+    commands
+}
+
+/// Exports the event context to an action, see the README for the variables
+pub fn event_env(
+    command: &mut Command,
+    trigger: ActionTrigger,
+    at: DateTime<Utc>,
+    enabled: bool,
+    location: Option<&str>,
+    next: Option<&EventInfo>,
+) {
+    command
+        .env("HSW_TRIGGER", trigger.to_string())
+        .env("HSW_EVENT_TIME", at.to_rfc3339())
+        .env("HSW_ENABLED", if enabled { "1" } else { "0" });
+    if let Some(location) = location {
+        command.env("HSW_LOCATION", location);
+    }
+    if let Some(next) = next {
+        command
+            .env("HSW_NEXT_TRIGGER", next.trigger.to_string())
+            .env("HSW_NEXT_EVENT_TIME", next.at.to_rfc3339());
+    }
+}
+
+/// Counts the actions that haven't exited yet
+#[derive(Clone, Default)]
+pub struct RunningActions(Arc<(Mutex<usize>, Condvar)>);
+
+impl RunningActions {
+    fn start(&self) {
+        *self.0.0.lock().unwrap() += 1;
+    }
+
+    fn finish(&self) {
+        *self.0.0.lock().unwrap() -= 1;
+        self.0.1.notify_all();
+    }
+
+    /// Blocks until every action exited or `timeout` passed, returns whether all exited
+    pub fn wait_idle(&self, timeout: Duration) -> bool {
+        let (running, idle) = &*self.0;
+        idle.wait_timeout_while(running.lock().unwrap(), timeout, |running| *running > 0)
+            .map(|(_, result)| !result.timed_out())
+            .unwrap()
+    }
+}
+
+/// Pipes the output of `command` so `reap` logs it, see `log_actions`
+pub fn capture_output(command: &mut Command) {
+    command.stdout(Stdio::piped()).stderr(Stdio::piped());
+}
+
+/// Starts `command` in a process group of its own, so a timed out action is killed along with
+/// every process it started
+pub fn own_process_group(command: &mut Command) {
+    #[cfg(unix)]
+    command.process_group(0);
+    #[cfg(not(unix))]
+    let _ = command;
+}
+
+/// Logs every line of `pipe` on its own thread until every process of the action closed it
+fn log_lines(
+    pipe: impl Read + Send + 'static,
+    trigger: ActionTrigger,
+    level: log::Level,
+) -> JoinHandle<()> {
+    std::thread::spawn(move || {
+        for line in BufReader::new(pipe).lines().map_while(Result::ok) {
+            log::log!(level, "{} action: {line}", logging::paint(trigger));
+        }
+    })
+}
+
+/// Kills `child` and, if it leads one, its process group
+fn kill(child: &mut Child) -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        let group = nix::unistd::Pid::from_raw(child.id() as i32);
+        // not a group leader unless spawned through `own_process_group`
+        if nix::unistd::getpgid(Some(group)) == Ok(group) {
+            return nix::sys::signal::killpg(group, nix::sys::signal::Signal::SIGKILL)
+                .map_err(io::Error::from);
+        }
+    }
+    child.kill()
+}
+
+/// Waits for `child`, killing it once `timeout` passed
+fn wait(
+    child: &mut Child,
+    trigger: ActionTrigger,
+    timeout: Option<Duration>,
+) -> io::Result<ExitStatus> {
+    let Some(timeout) = timeout else {
+        return child.wait();
+    };
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(status);
+        }
+        if Instant::now() >= deadline {
+            log::warn!(
+                "{} action still running after {}s, killing it",
+                logging::paint(trigger),
+                timeout.as_secs()
+            );
+            kill(child)?;
+            return child.wait();
+        }
+        sleep(POLL_INTERVAL);
+    }
+}
+
+/// Waits for `child` on its own thread, tracking it in `running`. The exit code is reported back
+/// to the daemon through `report` if given, an action killed after `timeout` has none.
+///
+/// Output captured with `capture_output` is logged along with the exit status, which waits for
+/// every process of the action to close its output.
+pub fn reap(
+    mut child: Child,
+    trigger: ActionTrigger,
+    running: RunningActions,
+    report: Option<Sender<Request>>,
+    timeout: Option<Duration>,
+) {
+    running.start();
+    std::thread::spawn(move || {
+        let readers = [
+            child
+                .stdout
+                .take()
+                .map(|out| log_lines(out, trigger, log::Level::Info)),
+            child
+                .stderr
+                .take()
+                .map(|err| log_lines(err, trigger, log::Level::Warn)),
+        ];
+        let captured = readers.iter().any(Option::is_some);
+        let exit_code = match wait(&mut child, trigger, timeout) {
+            Ok(status) => {
+                for reader in readers.into_iter().flatten() {
+                    let _ = reader.join();
+                }
+                if captured {
+                    log::info!("{} action exited with {status}", logging::paint(trigger));
+                }
+                status.code()
+            }
+            Err(err) => {
+                log::error!(
+                    "Failed to wait for {} action: {err}",
+                    logging::paint(trigger)
+                );
+                None
+            }
+        };
+        running.finish();
+        if let Some(sender) = report {
+            // nobody is left to report to if the daemon stopped meanwhile
+            let _ = sender.send(Action::ActionFinished { trigger, exit_code }.into());
+        }
+    });
+}
+
+/// Sends `action` to the daemon after `delay` without blocking the caller, used to let the
+/// display stack settle after a resume before catching up
+pub fn send_delayed(sender: Sender<Request>, action: Action, delay: Duration) {
+    if delay.is_zero() {
+        let _ = sender.send(action.into());
+        return;
+    }
+    std::thread::spawn(move || {
+        sleep(delay);
+        // the daemon may have stopped while waiting
+        let _ = sender.send(action.into());
+    });
+}
+
+/// Reads `pipe` to its end on a thread of its own, so the child never blocks on a full pipe
+fn drain(pipe: Option<impl Read + Send + 'static>) -> JoinHandle<String> {
+    std::thread::spawn(move || {
+        let mut output = vec![];
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut output);
+        }
+        String::from_utf8_lossy(&output).into_owned()
+    })
+}
+
+/// Runs `validator` with `config_path` as its argument, rejecting the reload on a nonzero exit
+/// or once `timeout` passes. The validator's output is part of the rejection.
+pub fn validate_reload(
+    config: &Configuration,
+    validator: &str,
+    config_path: &str,
+    timeout: Duration,
+) -> crate::error::Result<()> {
+    // the validator gets the path as a positional parameter, so this needs a POSIX shell
+    #[cfg(not(windows))]
+    let mut validate = {
+        let mut validate = Command::new("sh");
+        validate
+            .arg(shell_flag(config))
+            .arg(format!("{validator} \"$1\""))
+            .arg("hyprsunrisewatcher")
+            .arg(config_path);
+        validate
+    };
+    // cmd has no positional parameters, the path is quoted into the command line instead
+    #[cfg(windows)]
+    let mut validate = command(config, &format!("{validator} \"{config_path}\""));
+    let mut child = validate
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let stdout = drain(child.stdout.take());
+    let stderr = drain(child.stderr.take());
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if Instant::now() >= deadline {
+            child.kill()?;
+            child.wait()?;
+            return Err(Error::ReloadRejected(format!(
+                "{validator} timed out after {}s",
+                timeout.as_secs()
+            )));
+        }
+        sleep(POLL_INTERVAL);
+    };
+    let output = [stdout, stderr]
+        .map(|reader| reader.join().unwrap_or_default())
+        .concat();
+    if status.success() {
+        return Ok(());
+    }
+    Err(Error::ReloadRejected(format!(
+        "{validator} exited with {status}: {}",
+        output.trim()
+    )))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{actions::Action, config::Configuration, scheduler::ActionTrigger};
+
+    use std::{process::Command, sync::mpsc::channel, time::Duration};
+
+    use chrono::DateTime;
+
+    use super::{
+        RunningActions, capture_output, command, commands, detached, event_env, own_process_group,
+        reap, send_delayed, validate_reload,
+    };
+
+    #[test]
+    fn command_uses_plain_shell_by_default() {
+        let config = Configuration::default();
+
+        let command = command(&config, "true");
+
+        assert_eq!(command.get_program(), "sh");
+        assert_eq!(command.get_args().collect::<Vec<_>>(), ["-c", "true"]);
+    }
+
+    #[test]
+    fn command_uses_login_shell() {
+        let config = Configuration {
+            login_shell: true,
+            ..Default::default()
+        };
+
+        let command = command(&config, "true");
+
+        assert_eq!(command.get_args().collect::<Vec<_>>(), ["-lc", "true"]);
+    }
+
+    #[test]
+    fn command_uses_configured_shell() {
+        let config = Configuration {
+            shell: "bash".to_string(),
+            shell_args: Some(vec![
+                "-O".to_string(),
+                "extglob".to_string(),
+                "-c".to_string(),
+            ]),
+            ..Default::default()
+        };
+
+        let command = command(&config, "true");
+
+        assert_eq!(command.get_program(), "bash");
+        assert_eq!(
+            command.get_args().collect::<Vec<_>>(),
+            ["-O", "extglob", "-c", "true"]
+        );
+    }
+
+    #[test]
+    fn commands_expand_output_placeholder() {
+        let config = Configuration::default();
+        let outputs = ["DP-1".to_string(), "HDMI-A-1".to_string()];
+
+        let commands = commands(&config, "wlsunset -o {output}", &outputs, false);
+
+        let args = commands
+            .iter()
+            .map(|c| c.get_args().nth(1).unwrap().to_owned())
+            .collect::<Vec<_>>();
+        assert_eq!(args, ["wlsunset -o DP-1", "wlsunset -o HDMI-A-1"]);
+    }
+
+    #[test]
+    fn commands_without_placeholder_run_once() {
+        let config = Configuration::default();
+        let outputs = ["DP-1".to_string(), "HDMI-A-1".to_string()];
+
+        let commands = commands(&config, "true", &outputs, false);
+
+        assert_eq!(commands.len(), 1);
+        let outputs_env = commands[0]
+            .get_envs()
+            .find(|(key, _)| *key == "HSW_OUTPUTS")
+            .and_then(|(_, value)| value);
+        assert_eq!(outputs_env.unwrap(), "DP-1 HDMI-A-1");
+    }
+
+    #[test]
+    fn event_env_describes_the_event() {
+        let mut command = Command::new("true");
+        let at = DateTime::from_timestamp(1752414761, 0).unwrap();
+
+        event_env(
+            &mut command,
+            ActionTrigger::Sunset,
+            at,
+            false,
+            Some("Berlin"),
+            None,
+        );
+
+        let env = command
+            .get_envs()
+            .map(|(key, value)| (key.to_str().unwrap(), value.unwrap().to_str().unwrap()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            env,
+            [
+                ("HSW_ENABLED", "0"),
+                ("HSW_EVENT_TIME", "2025-07-13T13:52:41+00:00"),
+                ("HSW_LOCATION", "Berlin"),
+                ("HSW_TRIGGER", "sunset"),
+            ]
+        );
+    }
+
+    #[test]
+    fn chatty_validator_is_not_timed_out() {
+        let config = Configuration::default();
+
+        // more than a pipe buffer holds
+        let result = validate_reload(
+            &config,
+            "head -c 1000000 /dev/zero; head -c 1000000 /dev/zero >&2; test -n",
+            "config.toml",
+            Duration::from_secs(5),
+        );
+
+        assert!(result.is_ok(), "{result:?}");
+    }
+
+    #[test]
+    fn validator_accepts_on_success() {
+        let config = Configuration::default();
+
+        let result = validate_reload(&config, "test -n", "config.toml", Duration::from_secs(5));
+
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn validator_rejects_with_output() {
+        let config = Configuration::default();
+
+        let err = validate_reload(
+            &config,
+            "! echo rejected",
+            "config.toml",
+            Duration::from_secs(5),
+        )
+        .unwrap_err();
+
+        assert!(err.to_string().contains("rejected config.toml"));
+    }
+
+    #[test]
+    fn validator_times_out() {
+        let config = Configuration::default();
+
+        let err = validate_reload(
+            &config,
+            "sleep 5 && test -n",
+            "",
+            Duration::from_millis(100),
+        )
+        .unwrap_err();
+
+        assert!(err.to_string().contains("timed out"));
+    }
+
+    #[test]
+    fn wait_idle_waits_for_reaped_actions() {
+        let config = Configuration::default();
+        let running = RunningActions::default();
+        let child = command(&config, "sleep 0.1").spawn().unwrap();
+
+        reap(child, ActionTrigger::Sunset, running.clone(), None, None);
+
+        assert!(!running.wait_idle(Duration::ZERO));
+        assert!(running.wait_idle(Duration::from_secs(5)));
+    }
+
+    #[test]
+    fn reap_drains_captured_output() {
+        let config = Configuration::default();
+        let running = RunningActions::default();
+        let (sender, receiver) = channel();
+        let mut command = command(&config, "seq 100000; echo failed >&2; exit 3");
+        capture_output(&mut command);
+
+        reap(
+            command.spawn().unwrap(),
+            ActionTrigger::Sunset,
+            running.clone(),
+            Some(sender),
+            None,
+        );
+
+        assert!(running.wait_idle(Duration::from_secs(5)));
+        assert_eq!(
+            receiver.recv().unwrap().action,
+            Action::ActionFinished {
+                trigger: ActionTrigger::Sunset,
+                exit_code: Some(3)
+            }
+        );
+    }
+
+    #[test]
+    fn reap_kills_actions_after_timeout() {
+        let config = Configuration::default();
+        let running = RunningActions::default();
+        let (sender, receiver) = channel();
+        let child = command(&config, "exec sleep 10").spawn().unwrap();
+
+        reap(
+            child,
+            ActionTrigger::Dusk,
+            running.clone(),
+            Some(sender),
+            Some(Duration::from_millis(100)),
+        );
+
+        assert!(running.wait_idle(Duration::from_secs(5)));
+        assert_eq!(
+            receiver.recv().unwrap().action,
+            Action::ActionFinished {
+                trigger: ActionTrigger::Dusk,
+                exit_code: None
+            }
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn reap_kills_whole_pipeline_after_timeout() {
+        let config = Configuration::default();
+        let running = RunningActions::default();
+        let (sender, receiver) = channel();
+        // neither process is the shell, both hold the captured output open
+        let mut command = command(&config, "sleep 10 | cat");
+        capture_output(&mut command);
+        own_process_group(&mut command);
+
+        reap(
+            command.spawn().unwrap(),
+            ActionTrigger::Dusk,
+            running.clone(),
+            Some(sender),
+            Some(Duration::from_millis(100)),
+        );
+
+        assert!(running.wait_idle(Duration::from_secs(5)));
+        assert_eq!(
+            receiver.recv().unwrap().action,
+            Action::ActionFinished {
+                trigger: ActionTrigger::Dusk,
+                exit_code: None
+            }
+        );
+    }
+
+    #[test]
+    fn send_delayed_does_not_block() {
+        let (sender, receiver) = channel();
+
+        send_delayed(sender.clone(), Action::Enable, Duration::from_millis(100));
+        send_delayed(sender, Action::Disable, Duration::ZERO);
+
+        assert_eq!(receiver.recv().unwrap().action, Action::Disable);
+        assert_eq!(receiver.recv().unwrap().action, Action::Enable);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn detached_action_is_reparented() {
+        let config = Configuration::default();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("parent");
+        let action = format!(
+            "sleep 0.2; cut -d' ' -f4 /proc/$$/stat > {}",
+            path.display()
+        );
+
+        let mut wrapper = detached(&config, &action).spawn().unwrap();
+        let wrapper_pid = wrapper.id().to_string();
+        wrapper.wait().unwrap();
+        let mut parent = String::new();
+        for _ in 0..50 {
+            std::thread::sleep(Duration::from_millis(100));
+            parent = std::fs::read_to_string(&path).unwrap_or_default();
+            if !parent.is_empty() {
+                break;
+            }
+        }
+
+        assert!(!parent.trim().is_empty());
+        assert_ne!(parent.trim(), wrapper_pid);
+    }
+}
@@ -1,12 +1,23 @@
-use std::fmt::{Debug, Display};
+use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    fmt::{Debug, Display},
+    str::FromStr,
+};
 
+use bincode::{Decode, Encode};
 use chrono::{Days, TimeDelta, prelude::*};
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 use sunrise::{Coordinates, SolarDay, SolarEvent};
 
 use crate::{
-    config::{Actions, Configuration, ManualTimeStamp},
-    info::EventInfo,
+    config::{
+        self, ActionCommand, Actions, Configuration, Fallback, ManualConfig, Offsets,
+        TriggerSettings, Twilight,
+    },
+    date,
+    info::{CurrentEvent, EventInfo},
+    precompute::PrecomputedEvents,
 };
 
 pub struct Scheduler<T: Trigger> {
@@ -16,32 +27,80 @@ pub struct Scheduler<T: Trigger> {
 
 pub struct TriggerSource {
     event_source: Box<dyn EventSource>,
+    location: Option<String>,
 }
 
 impl TriggerSource {
     pub fn from_config(config: &Configuration) -> crate::error::Result<Self> {
-        if let Some(auto) = &config.automatic {
+        Self::from_settings(config.trigger_settings())
+    }
+
+    /// The schedule of `settings` at the configured coordinates
+    pub fn from_settings(settings: TriggerSettings) -> crate::error::Result<Self> {
+        Self::with_location(settings, false)
+    }
+
+    /// The schedule of the trigger thread, which reads nothing else of the configuration. Unlike
+    /// [`from_settings`](Self::from_settings) it asks GeoClue with `source = "geoclue"`.
+    pub fn locate(settings: TriggerSettings) -> crate::error::Result<Self> {
+        Self::with_location(settings, true)
+    }
+
+    fn with_location(settings: TriggerSettings, geoclue: bool) -> crate::error::Result<Self> {
+        if let Some(auto) = settings.automatic {
+            let (latitude, longitude) = if geoclue {
+                auto.located_coordinates()?
+            } else {
+                auto.coordinates()?
+            };
+            let mut location = LocationInfo::try_from((latitude, longitude))?
+                .with_twilight(auto.twilight)
+                .with_offsets(auto.offsets)
+                .with_fallback(auto.fallback)
+                .with_extra_events(
+                    [ActionTrigger::SolarNoon, ActionTrigger::SolarMidnight]
+                        .into_iter()
+                        .filter(|trigger| !settings.actions.get(*trigger).is_empty()),
+                );
+            if let Some(path) = &auto.event_cache {
+                let events = PrecomputedEvents::load(path)?;
+                if !events.matches(latitude, longitude, auto.twilight) {
+                    return Err(crate::error::Error::InvalidEventCache(format!(
+                        "{path} was computed for lat: {} long: {} twilight: {:?}",
+                        events.latitude, events.longitude, events.twilight
+                    )));
+                }
+                location = location.with_precomputed(events);
+            }
             Ok(Self {
-                event_source: Box::new(Scheduler::automatic(
-                    (auto.latitude, auto.longitude),
-                    config.actions.clone(),
-                )),
+                event_source: Box::new(Scheduler::automatic(location, settings.actions.clone())),
+                location: auto.location_name().map(str::to_string),
             })
-        } else if let Some(manual) = &config.manual {
+        } else if let Some(manual) = settings.manual {
             Ok(TriggerSource {
-                event_source: Box::new(Scheduler::manual(
-                    manual.time_stamps.clone(),
-                    config.actions.clone(),
-                )),
+                event_source: Box::new(Scheduler::manual(manual.clone(), settings.actions.clone())),
+                location: None,
             })
         } else {
-            Err(crate::error::Error::InvalidConfiguration.into())
+            Err(crate::error::Error::InvalidConfiguration(
+                "neither [manual] nor [automatic] is set".to_string(),
+            ))
         }
     }
 }
+/// What the trigger thread needs to know of the next event, unlike [`EventInfo`] without a
+/// description of its commands to build on every tick
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PendingEvent {
+    pub trigger: ActionTrigger,
+    pub at: DateTime<Utc>,
+    /// Whether any command runs at the event
+    pub has_commands: bool,
+}
+
 #[derive(Debug)]
 pub struct EventCache {
-    next_event: Option<EventInfo>,
+    next_event: Option<PendingEvent>,
     is_triggerd: bool,
 }
 
@@ -56,21 +115,44 @@ impl EventCache {
     pub fn should_trigger(
         &mut self,
         date: DateTime<Utc>,
-        event: Option<EventInfo>,
-    ) -> Option<String> {
+        event: Option<PendingEvent>,
+    ) -> Option<(ActionTrigger, DateTime<Utc>)> {
+        // the pending event is replaced by its successor as soon as it is reached, fire it first
+        let due = self.due(date);
         if self.next_event != event {
             self.next_event = event;
             self.is_triggerd = false;
         }
+        due.or_else(|| self.due(date))
+    }
+
+    /// The pending event if it was due by `date` but didn't fire, e.g. because the clock jumped
+    /// past it during suspend. It is marked fired.
+    pub fn missed(&mut self, date: DateTime<Utc>) -> Option<(ActionTrigger, DateTime<Utc>)> {
+        match &self.next_event {
+            Some(ev) if ev.has_commands && !self.is_triggerd && ev.at <= date => {
+                self.is_triggerd = true;
+                Some((ev.trigger, ev.at))
+            }
+            _ => None,
+        }
+    }
+
+    /// The event the cache is waiting for
+    pub fn pending(&self) -> Option<&PendingEvent> {
+        self.next_event.as_ref()
+    }
+
+    fn due(&mut self, date: DateTime<Utc>) -> Option<(ActionTrigger, DateTime<Utc>)> {
         if let Some(ev) = &self.next_event
-            && let Some(action) = &ev.action
+            && ev.has_commands
             && !self.is_triggerd
         {
             //10:00 - 9:59 => 1m
             let duration = date - ev.at;
             if duration >= TimeDelta::zero() && duration < TimeDelta::minutes(1) {
                 self.is_triggerd = true;
-                Some(action.clone())
+                Some((ev.trigger, ev.at))
             } else {
                 None
             }
@@ -88,7 +170,24 @@ impl Default for EventCache {
 
 impl EventSource for TriggerSource {
     fn next_event_at(&self, date: DateTime<Utc>) -> Option<EventInfo> {
-        self.event_source.next_event_at(date)
+        self.event_source
+            .next_event_at(date)
+            .map(|event| EventInfo {
+                location: self.location.clone(),
+                ..event
+            })
+    }
+
+    fn next_pending_at(&self, date: DateTime<Utc>) -> Option<PendingEvent> {
+        self.event_source.next_pending_at(date)
+    }
+
+    fn current_event_at(&self, date: DateTime<Utc>) -> Option<CurrentEvent> {
+        self.event_source.current_event_at(date)
+    }
+
+    fn commands_at(&self, trigger: ActionTrigger, at: DateTime<Utc>) -> &[ActionCommand] {
+        self.event_source.commands_at(trigger, at)
     }
 }
 
@@ -98,43 +197,210 @@ impl<T: Trigger> EventSource for Scheduler<T> {
         trigger.map(|(action, at)| EventInfo {
             at,
             trigger: action,
-            action: self.get_action(action),
+            action: config::describe(self.commands_at(action, at)),
+            location: None,
+            polar: self.trigger.polar_at(date),
+        })
+    }
+
+    fn next_pending_at(&self, date: DateTime<Utc>) -> Option<PendingEvent> {
+        let (trigger, at) = self.trigger.next_action_at(date)?;
+        Some(PendingEvent {
+            trigger,
+            at,
+            has_commands: !self.commands_at(trigger, at).is_empty(),
         })
     }
+
+    fn current_event_at(&self, date: DateTime<Utc>) -> Option<CurrentEvent> {
+        self.trigger.current_action_at(date)
+    }
+
+    fn commands_at(&self, trigger: ActionTrigger, at: DateTime<Utc>) -> &[ActionCommand] {
+        self.trigger.commands_at(trigger, at, &self.actions)
+    }
 }
 pub trait EventSource {
     fn next_event_at(&self, date: DateTime<Utc>) -> Option<EventInfo>;
 
-    fn should_trigger(&self, date: DateTime<Utc>, cache: &mut EventCache) -> Option<String> {
-        let next = self.next_event_at(date);
+    /// Like `next_event_at`, but only borrows the commands, for the trigger thread asking on
+    /// every tick
+    fn next_pending_at(&self, date: DateTime<Utc>) -> Option<PendingEvent>;
+
+    /// The last event before `date`, i.e. the one whose period `date` falls into
+    fn current_event_at(&self, date: DateTime<Utc>) -> Option<CurrentEvent>;
+
+    /// The commands to run for the `trigger` event at `at`
+    fn commands_at(&self, trigger: ActionTrigger, at: DateTime<Utc>) -> &[ActionCommand];
+
+    /// The trigger and time of the event that is due at `date`, if any
+    fn should_trigger(
+        &self,
+        date: DateTime<Utc>,
+        cache: &mut EventCache,
+    ) -> Option<(ActionTrigger, DateTime<Utc>)> {
+        let next = self.next_pending_at(date);
         cache.should_trigger(date, next)
     }
+
+    /// The next `count` events after `date`, stops early if the schedule doesn't advance
+    fn upcoming_events(&self, date: DateTime<Utc>, count: usize) -> Vec<EventInfo> {
+        collect_events(self, date, DateTime::<Utc>::MAX_UTC, count)
+    }
+
+    /// The events from `start` up to but excluding `end`, in order. Stops after
+    /// [`MAX_EVENTS_BETWEEN`] events or once the schedule doesn't advance.
+    fn events_between(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Vec<EventInfo> {
+        collect_events(self, start, end, MAX_EVENTS_BETWEEN)
+    }
+}
+
+/// Upper bound for the events collected by [`EventSource::events_between`], a year of six daily
+/// events with room to spare
+pub const MAX_EVENTS_BETWEEN: usize = 4096;
+
+fn collect_events<S: EventSource + ?Sized>(
+    source: &S,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    count: usize,
+) -> Vec<EventInfo> {
+    let mut events: Vec<EventInfo> = vec![];
+    let mut from = start;
+    while events.len() < count
+        && let Some(event) = source.next_event_at(from)
+        && event.at < end
+    {
+        if events.last().is_some_and(|last| last.at >= event.at) {
+            break;
+        }
+        from = event.at + TimeDelta::seconds(1);
+        events.push(event);
+    }
+    events
 }
 
 pub trait Trigger {
     fn next_action_at(&self, date: DateTime<Utc>) -> Option<(ActionTrigger, DateTime<Utc>)>;
+
+    /// The trigger of the period `date` falls into
+    fn current_action_at(&self, date: DateTime<Utc>) -> Option<CurrentEvent>;
+
+    /// Whether the sun neither rises nor sets on the day of `date`
+    fn polar_at(&self, _date: DateTime<Utc>) -> Option<Polar> {
+        None
+    }
+
+    /// The commands of the `trigger` event at `at`, those of `trigger` unless overridden
+    fn commands_at<'a>(
+        &'a self,
+        trigger: ActionTrigger,
+        _at: DateTime<Utc>,
+        actions: &'a Actions,
+    ) -> &'a [ActionCommand] {
+        actions.get(trigger)
+    }
+}
+
+/// A day on which the sun doesn't cross the horizon
+#[derive(Serialize, Deserialize, Encode, Decode, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Polar {
+    /// The sun stays above the horizon, the last event is the sunrise
+    Day,
+    /// The sun stays below the horizon, the last event is the dusk
+    Night,
+}
+
+impl Display for Polar {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Polar::Day => f.write_str("polar day, the sun doesn't set today"),
+            Polar::Night => f.write_str("polar night, the sun doesn't rise today"),
+        }
+    }
 }
 
-#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+/// Serialized and displayed in snake case, e.g. `solar_noon`, parsed case insensitively
+#[derive(Serialize, Encode, Decode, Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "snake_case")]
 pub enum ActionTrigger {
     Sunrise,
     Sunset,
     Dusk,
     Dawn,
+    /// The sun's highest point
+    SolarNoon,
+    /// The sun's lowest point, 12 hours after solar noon
+    SolarMidnight,
 }
 impl Display for ActionTrigger {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        Debug::fmt(&self, f)
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for ActionTrigger {
+    type Err = crate::error::Error;
+
+    /// Case insensitive, also accepts a few common synonyms
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "sunrise" | "sunup" => Ok(ActionTrigger::Sunrise),
+            "sunset" | "sundown" => Ok(ActionTrigger::Sunset),
+            "dusk" | "nightfall" => Ok(ActionTrigger::Dusk),
+            "dawn" | "daybreak" => Ok(ActionTrigger::Dawn),
+            "solarnoon" | "solar_noon" | "solar-noon" => Ok(ActionTrigger::SolarNoon),
+            "solarmidnight" | "solar_midnight" | "solar-midnight" => {
+                Ok(ActionTrigger::SolarMidnight)
+            }
+            _ => Err(crate::error::Error::InvalidAction(format!(
+                "{s}, expected one of sunrise, sunset, dusk, dawn, solar_noon or solar_midnight"
+            ))),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ActionTrigger {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
     }
 }
 
 impl ActionTrigger {
+    /// The canonical name, the same as serialized
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ActionTrigger::Sunrise => "sunrise",
+            ActionTrigger::Sunset => "sunset",
+            ActionTrigger::Dusk => "dusk",
+            ActionTrigger::Dawn => "dawn",
+            ActionTrigger::SolarNoon => "solar_noon",
+            ActionTrigger::SolarMidnight => "solar_midnight",
+        }
+    }
+
     pub fn next(self) -> Self {
         match self {
-            ActionTrigger::Sunrise => ActionTrigger::Sunset,
-            ActionTrigger::Sunset => ActionTrigger::Dusk,
-            ActionTrigger::Dusk => ActionTrigger::Dawn,
             ActionTrigger::Dawn => ActionTrigger::Sunrise,
+            ActionTrigger::Sunrise => ActionTrigger::SolarNoon,
+            ActionTrigger::SolarNoon => ActionTrigger::Sunset,
+            ActionTrigger::Sunset => ActionTrigger::Dusk,
+            ActionTrigger::Dusk => ActionTrigger::SolarMidnight,
+            ActionTrigger::SolarMidnight => ActionTrigger::Dawn,
+        }
+    }
+
+    pub fn previous(self) -> Self {
+        match self {
+            ActionTrigger::Dawn => ActionTrigger::SolarMidnight,
+            ActionTrigger::Sunrise => ActionTrigger::Dawn,
+            ActionTrigger::SolarNoon => ActionTrigger::Sunrise,
+            ActionTrigger::Sunset => ActionTrigger::SolarNoon,
+            ActionTrigger::Dusk => ActionTrigger::Sunset,
+            ActionTrigger::SolarMidnight => ActionTrigger::Dusk,
         }
     }
 }
@@ -148,64 +414,213 @@ impl Scheduler<LocationInfo> {
     }
 }
 
-impl Trigger for Vec<ManualTimeStamp> {
-    fn next_action_at(&self, date: DateTime<Utc>) -> Option<(ActionTrigger, DateTime<Utc>)> {
-        let local_dt = date.with_timezone(&Local);
-        let min = self.iter().min_by(move |a, b| {
-            (a.trigger_time - local_dt.time()).cmp(&(b.trigger_time - local_dt.time()))
-        });
+/// Days searched for a time stamp that applies, a week and the day of the query
+const MANUAL_SEARCH_DAYS: usize = 8;
 
-        min.map(|m| {
-            let now = Local::now()
-                .with_time(m.trigger_time)
-                .unwrap()
-                .with_timezone(&Utc);
-            if now < date {
-                (m.action, now.checked_add_days(Days::new(1)).unwrap())
-            } else {
-                (m.action, now)
-            }
+impl Trigger for ManualConfig {
+    /// The first time stamp strictly after `date` on the clock of the time stamps, the first one
+    /// of the next day it applies on once all of today's have passed. See
+    /// [`ManualConfig::instant`] for days daylight saving time starts or ends.
+    fn next_action_at(&self, date: DateTime<Utc>) -> Option<(ActionTrigger, DateTime<Utc>)> {
+        let today = self.wall_clock(date).date();
+        today.iter_days().take(MANUAL_SEARCH_DAYS).find_map(|day| {
+            self.on(day)
+                .filter(|(_, at)| *at > date)
+                .min_by_key(|(_, at)| *at)
+                .map(|(m, at)| (m.action, at))
         })
     }
-}
-impl Scheduler<Vec<ManualTimeStamp>> {
-    pub fn manual(time_stamps: Vec<ManualTimeStamp>, actions: Actions) -> Self {
-        Self {
-            actions,
-            trigger: time_stamps,
+
+    /// The last time stamp at or before `date` on the clock of the time stamps, the last one of
+    /// the previous day it applies on before today's first
+    fn current_action_at(&self, date: DateTime<Utc>) -> Option<CurrentEvent> {
+        let today = self.wall_clock(date).date();
+        (0..MANUAL_SEARCH_DAYS as u64)
+            .filter_map(|days| today.checked_sub_days(Days::new(days)))
+            .find_map(|day| {
+                self.on(day)
+                    .filter(|(_, at)| *at <= date)
+                    .max_by_key(|(_, at)| *at)
+                    .map(|(m, at)| CurrentEvent {
+                        trigger: m.action,
+                        since: Some(at),
+                    })
+            })
+    }
+
+    /// The commands of the time stamp at `at`, which may override those of `trigger`
+    fn commands_at<'a>(
+        &'a self,
+        trigger: ActionTrigger,
+        at: DateTime<Utc>,
+        actions: &'a Actions,
+    ) -> &'a [ActionCommand] {
+        let wall_clock = self.wall_clock(at);
+        match self.time_stamps.iter().find(|m| {
+            m.action == trigger
+                && m.trigger_time == wall_clock.time()
+                && m.applies_on(wall_clock.date())
+        }) {
+            Some(m) => m.commands(actions),
+            None => actions.get(trigger),
         }
     }
 }
 
-impl<T: Trigger> Scheduler<T> {
-    pub fn get_action(&self, trigger: ActionTrigger) -> Option<String> {
-        self.actions.get(trigger)
+impl Scheduler<ManualConfig> {
+    pub fn manual(manual: ManualConfig, actions: Actions) -> Self {
+        Self {
+            actions,
+            trigger: manual,
+        }
     }
 }
 
+/// Where solar events are computed for.
+///
+/// Tuples are `(latitude, longitude)` in degrees, the argument order of [`Coordinates::new`],
+/// regardless of the field order in [`crate::config::AutomaticConfig`].
 pub struct LocationInfo {
     coords: Coordinates,
+    twilight: Twilight,
+    precomputed: Option<PrecomputedEvents>,
+    offsets: Offsets,
+    fallback: Fallback,
+    extra_events: Vec<ActionTrigger>,
+    solar_cache: SolarCache,
+}
+
+/// The solar times of the dates a [`LocationInfo`] was asked for. Coordinates and twilight are
+/// fixed per location, a reload builds a new one, so the date is all the key needs.
+#[derive(Default)]
+struct SolarCache {
+    times: RefCell<BTreeMap<NaiveDate, SolarTimes>>,
+    /// How often `compute` ran, for the tests
+    #[cfg(test)]
+    computed: std::cell::Cell<usize>,
+}
+
+impl SolarCache {
+    fn get_or_compute(&self, date: NaiveDate, compute: impl FnOnce() -> SolarTimes) -> SolarTimes {
+        if let Some(times) = self.times.borrow().get(&date) {
+            return *times;
+        }
+        let times = compute();
+        #[cfg(test)]
+        self.computed.set(self.computed.get() + 1);
+        self.times.borrow_mut().insert(date, times);
+        times
+    }
+
+    /// Drops the dates before `date`
+    fn evict_before(&self, date: NaiveDate) {
+        let mut times = self.times.borrow_mut();
+        *times = times.split_off(&date);
+    }
 }
-impl From<(f64, f64)> for LocationInfo {
-    fn from(value: (f64, f64)) -> Self {
-        Coordinates::new(value.0, value.1).unwrap().into()
+
+impl TryFrom<(f64, f64)> for LocationInfo {
+    type Error = crate::error::Error;
+
+    fn try_from(value: (f64, f64)) -> Result<Self, Self::Error> {
+        let coords = Coordinates::new(value.0, value.1)
+            .ok_or(crate::error::Error::InvalidCoordinates(value.0, value.1))?;
+        Ok(coords.into())
     }
 }
 
 impl Trigger for LocationInfo {
     fn next_action_at(&self, date: DateTime<Utc>) -> Option<(ActionTrigger, DateTime<Utc>)> {
-        let interval = self.interval_at(date);
+        let interval = self.interval_at(date)?;
+
+        Some((interval.next, interval.end))
+    }
+
+    fn current_action_at(&self, date: DateTime<Utc>) -> Option<CurrentEvent> {
+        let interval = self.interval_at(date)?;
+        Some(CurrentEvent {
+            trigger: interval.current_event(),
+            since: interval.start(),
+        })
+    }
 
-        Some((interval.event.next(), interval.end))
+    fn polar_at(&self, date: DateTime<Utc>) -> Option<Polar> {
+        let today = date.date_naive();
+        self.solar_times(today).polar(today, self.coords.lat())
     }
 }
 impl LocationInfo {
     pub fn new(coords: Coordinates) -> Self {
-        Self { coords }
+        Self {
+            coords,
+            twilight: Twilight::default(),
+            precomputed: None,
+            offsets: Offsets::default(),
+            fallback: Fallback::default(),
+            extra_events: vec![],
+            solar_cache: SolarCache::default(),
+        }
+    }
+
+    /// Also schedule `extra` of solar noon and solar midnight, which are left out unless actions
+    /// run at them
+    pub fn with_extra_events(mut self, extra: impl IntoIterator<Item = ActionTrigger>) -> Self {
+        self.extra_events = extra.into_iter().collect();
+        self
+    }
+
+    /// Use the local times of `fallback` for events that don't happen on a day
+    pub fn with_fallback(mut self, fallback: Fallback) -> Self {
+        self.fallback = fallback;
+        self
+    }
+
+    /// Move the events by `offsets`
+    pub fn with_offsets(mut self, offsets: Offsets) -> Self {
+        self.offsets = offsets;
+        self
+    }
+
+    /// Compute dawn and dusk for `twilight` instead of civil twilight
+    pub fn with_twilight(mut self, twilight: Twilight) -> Self {
+        self.twilight = twilight;
+        self
+    }
+
+    /// Prefer `events` over live computation for the dates it covers
+    pub fn with_precomputed(mut self, events: PrecomputedEvents) -> Self {
+        self.precomputed = Some(events);
+        self
+    }
+
+    /// The solar times of `date`, computed once per date and kept until the date is two days past
+    pub fn solar_times(&self, date: NaiveDate) -> SolarTimes {
+        self.solar_cache.get_or_compute(date, || {
+            self.precomputed
+                .as_ref()
+                .and_then(|p| p.get(date))
+                .unwrap_or_else(|| SolarTimes::compute(self.coords, date, self.twilight))
+                .with_fallback(date, &self.fallback, &Local)
+        })
     }
 
-    pub fn interval_at(&self, date: DateTime<Utc>) -> Interval {
-        Interval::new(self.coords, date)
+    /// The period `date` falls into, `None` if no event happens within a year
+    pub fn interval_at(&self, date: DateTime<Utc>) -> Option<Interval> {
+        let yesterday = date.date_naive().pred_opt()?;
+        if let Some(past) = yesterday.pred_opt() {
+            self.solar_cache.evict_before(past);
+        }
+        let days = yesterday
+            .iter_days()
+            .take(SEARCH_DAYS)
+            .map(|day| (day, self.solar_times(day)));
+        Interval::from_days(
+            date,
+            days,
+            &self.offsets,
+            &self.extra_events,
+            self.polar_at(date),
+        )
     }
 }
 impl From<Coordinates> for LocationInfo {
@@ -213,83 +628,231 @@ impl From<Coordinates> for LocationInfo {
         Self::new(value)
     }
 }
+/// Days searched for the next event, enough to get through the half year of polar day at the poles
+const SEARCH_DAYS: usize = 367;
+
 pub struct Interval {
-    #[allow(dead_code)]
-    start: DateTime<Utc>,
+    start: Option<DateTime<Utc>>,
     end: DateTime<Utc>,
     event: ActionTrigger,
+    next: ActionTrigger,
 }
 
-impl Interval {
-    fn new(coords: Coordinates, date: DateTime<Utc>) -> Self {
-        let now = date;
-        let relevant_days = [
-            SolarDay::new(
-                coords,
-                now.checked_sub_days(Days::new(1)).unwrap().date_naive(),
-            ),
-            SolarDay::new(coords, now.date_naive()),
-            SolarDay::new(
-                coords,
-                now.checked_add_days(Days::new(1)).unwrap().date_naive(),
-            ),
-        ];
+/// The transitions of a single day
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SolarTimes {
+    pub dawn: DateTime<Utc>,
+    pub sunrise: DateTime<Utc>,
+    pub sunset: DateTime<Utc>,
+    pub dusk: DateTime<Utc>,
+}
 
-        let today_dawn = relevant_days[1].event_time(SolarEvent::Dawn(sunrise::DawnType::Civil));
-        let yesterday_dusk =
-            relevant_days[0].event_time(SolarEvent::Dusk(sunrise::DawnType::Civil));
-        if yesterday_dusk < now && now < today_dawn {
-            return Self {
-                start: yesterday_dusk,
-                end: today_dawn,
-                event: ActionTrigger::Dusk,
-            };
+impl SolarTimes {
+    pub fn compute(coords: Coordinates, date: NaiveDate, twilight: Twilight) -> Self {
+        let day = SolarDay::new(coords, date);
+        Self {
+            dawn: day.event_time(SolarEvent::Dawn(twilight.into())),
+            sunrise: day.event_time(SolarEvent::Sunrise),
+            sunset: day.event_time(SolarEvent::Sunset),
+            dusk: day.event_time(SolarEvent::Dusk(twilight.into())),
         }
-        let today_sunrise = relevant_days[1].event_time(SolarEvent::Sunrise);
-        if today_dawn <= now && now < today_sunrise {
-            return Self {
-                start: today_dawn,
-                end: today_sunrise,
-                event: ActionTrigger::Dawn,
-            };
+    }
+
+    /// Replaces the events missing on `date` by the times of `fallback` on the clock of `tz`
+    fn with_fallback(mut self, date: NaiveDate, fallback: &Fallback, tz: &impl TimeZone) -> Self {
+        for (at, time) in [
+            (&mut self.dawn, fallback.dawn),
+            (&mut self.sunrise, fallback.sunrise),
+            (&mut self.sunset, fallback.sunset),
+            (&mut self.dusk, fallback.dusk),
+        ] {
+            if let Some(time) = time
+                && Self::is_missing(*at, date)
+                && let Some(local) = date::local_instant(date.and_time(time), tz)
+            {
+                *at = local.with_timezone(&Utc);
+            }
         }
-        let today_sunset = relevant_days[1].event_time(SolarEvent::Sunset);
-        if today_sunrise <= now && now < today_sunset {
-            return Self {
-                start: today_sunrise,
-                end: today_sunset,
-                event: ActionTrigger::Sunrise,
-            };
+        self
+    }
+
+    /// The events that don't happen on `date`, the sun stays above or below their elevation.
+    /// Their computed time is far off the date in that case.
+    pub fn missing(&self, date: NaiveDate) -> Vec<ActionTrigger> {
+        self.all()
+            .into_iter()
+            .filter(|(_, at)| Self::is_missing(*at, date))
+            .map(|(trigger, _)| trigger)
+            .collect()
+    }
+
+    /// The events that happen on `date` moved by `offsets`, in the order they happen. Solar noon
+    /// and solar midnight only if they are among `extra`.
+    pub fn events(
+        &self,
+        date: NaiveDate,
+        offsets: &Offsets,
+        extra: &[ActionTrigger],
+    ) -> impl Iterator<Item = (ActionTrigger, DateTime<Utc>)> {
+        let mut events = self
+            .all()
+            .into_iter()
+            .filter(|(trigger, at)| is_scheduled(*trigger, extra) && !Self::is_missing(*at, date))
+            .map(|(trigger, at)| (trigger, at + offsets.get(trigger)))
+            .collect::<Vec<_>>();
+        // offsets can move an event past its neighbour
+        events.sort_by_key(|(_, at)| *at);
+        events.into_iter()
+    }
+
+    /// Halfway between sunrise and sunset. The `sunrise` crate puts every event at the solar
+    /// transit plus or minus an hour angle, so this is the transit, which it doesn't expose. Far
+    /// off the date if either of them is missing.
+    fn solar_noon(&self) -> DateTime<Utc> {
+        let day = self.sunset - self.sunrise;
+        if day < TimeDelta::zero() || day > TimeDelta::days(1) {
+            return DateTime::<Utc>::MIN_UTC;
         }
-        let today_dusk = relevant_days[1].event_time(SolarEvent::Dusk(sunrise::DawnType::Civil));
-        if today_sunset <= now && now < today_dusk {
-            return Self {
-                start: today_sunset,
-                end: today_dusk,
-                event: ActionTrigger::Sunset,
-            };
+        self.sunrise + day / 2
+    }
+
+    /// Whether the sun stays up or down all of `date` at `latitude`
+    pub fn polar(&self, date: NaiveDate, latitude: f64) -> Option<Polar> {
+        let missing = self.missing(date);
+        if !missing.contains(&ActionTrigger::Sunrise) && !missing.contains(&ActionTrigger::Sunset) {
+            return None;
         }
-        let tomorrow_dawn = relevant_days[2].event_time(SolarEvent::Dawn(sunrise::DawnType::Civil));
-        Self {
-            start: today_dusk,
-            end: tomorrow_dawn,
-            event: ActionTrigger::Dusk,
+        // without sunrise and sunset the sun stays on the side of the horizon it is at noon
+        if noon_elevation(date, latitude) > SUNRISE_ELEVATION {
+            Some(Polar::Day)
+        } else {
+            Some(Polar::Night)
         }
     }
 
+    fn all(&self) -> [(ActionTrigger, DateTime<Utc>); 6] {
+        let noon = self.solar_noon();
+        [
+            (ActionTrigger::Dawn, self.dawn),
+            (ActionTrigger::Sunrise, self.sunrise),
+            (ActionTrigger::SolarNoon, noon),
+            (ActionTrigger::Sunset, self.sunset),
+            (ActionTrigger::Dusk, self.dusk),
+            (ActionTrigger::SolarMidnight, noon + TimeDelta::hours(12)),
+        ]
+    }
+
+    fn is_missing(at: DateTime<Utc>, date: NaiveDate) -> bool {
+        (at.date_naive() - date).num_days().abs() > 1
+    }
+}
+
+/// Whether `trigger` is scheduled, the sun's crossings always are, solar noon and solar midnight
+/// only if among `extra`
+fn is_scheduled(trigger: ActionTrigger, extra: &[ActionTrigger]) -> bool {
+    !matches!(
+        trigger,
+        ActionTrigger::SolarNoon | ActionTrigger::SolarMidnight
+    ) || extra.contains(&trigger)
+}
+
+/// The scheduled event before `trigger`, see [`is_scheduled`]
+fn previous_scheduled(trigger: ActionTrigger, extra: &[ActionTrigger]) -> ActionTrigger {
+    let mut previous = trigger.previous();
+    while !is_scheduled(previous, extra) {
+        previous = previous.previous();
+    }
+    previous
+}
+
+/// Degrees the sun's center is below the horizon at sunrise and sunset, refraction included
+const SUNRISE_ELEVATION: f64 = -0.833;
+
+/// Elevation of the sun in degrees at solar noon of `date` at `latitude`. The declination is
+/// computed the way the `sunrise` crate does, from the sun's mean anomaly and ecliptic longitude.
+fn noon_elevation(date: NaiveDate, latitude: f64) -> f64 {
+    let j2000 = NaiveDate::from_ymd_opt(2000, 1, 1).expect("valid date");
+    let days = (date - j2000).num_days() as f64;
+    let anomaly = (357.5291 + 0.98560028 * days).to_radians();
+    let center =
+        1.9148 * anomaly.sin() + 0.02 * (2.0 * anomaly).sin() + 0.0003 * (3.0 * anomaly).sin();
+    let longitude = anomaly + (center + 180.0 + 102.9372).to_radians();
+    let declination = (longitude.sin() * 23.44f64.to_radians().sin())
+        .asin()
+        .to_degrees();
+    90.0 - (latitude - declination).abs()
+}
+
+impl Interval {
+    #[cfg(test)]
+    fn new(coords: Coordinates, date: DateTime<Utc>) -> Self {
+        LocationInfo::new(coords).interval_at(date).unwrap()
+    }
+
+    /// Finds the events around `now` in the solar times of consecutive `days`, starting the day
+    /// before `now`.
+    ///
+    /// Days without some of the events, e.g. without sunset during polar day, are skipped over.
+    /// If none of the events happened since the first day, `polar` tells the current period.
+    fn from_days(
+        now: DateTime<Utc>,
+        days: impl Iterator<Item = (NaiveDate, SolarTimes)>,
+        offsets: &Offsets,
+        extra: &[ActionTrigger],
+        polar: Option<Polar>,
+    ) -> Option<Self> {
+        let mut last = None;
+        for (day, times) in days {
+            for (trigger, at) in times.events(day, offsets, extra) {
+                if at <= now {
+                    last = Some((trigger, at));
+                    continue;
+                }
+                let event = last.map(|(event, _)| event).unwrap_or(match polar {
+                    Some(Polar::Day) => ActionTrigger::Sunrise,
+                    Some(Polar::Night) => ActionTrigger::Dusk,
+                    None => previous_scheduled(trigger, extra),
+                });
+                return Some(Self {
+                    start: last.map(|(_, start)| start),
+                    end: at,
+                    event,
+                    next: trigger,
+                });
+            }
+        }
+        None
+    }
+
     pub fn current_event(&self) -> ActionTrigger {
         self.event
     }
+
+    /// When the current event happened, `None` if that was before yesterday during polar day
+    /// or night
+    pub fn start(&self) -> Option<DateTime<Utc>> {
+        self.start
+    }
 }
 
 #[cfg(test)]
 mod test {
-    use chrono::{DateTime, Utc};
+    use chrono::{DateTime, Datelike, Local, NaiveDate, NaiveTime, TimeDelta, Utc, Weekday};
     use sunrise::Coordinates;
 
-    use crate::scheduler::ActionTrigger;
+    use crate::{
+        config::{
+            ActionCommand, ActionCommands, Actions, AutomaticConfig, Configuration, Fallback,
+            ManualConfig, ManualTimeStamp, Offsets, Twilight,
+        },
+        info::CurrentEvent,
+        scheduler::ActionTrigger,
+    };
 
-    use super::Interval;
+    use super::{
+        EventCache, EventSource, Interval, LocationInfo, PendingEvent, Polar, Scheduler,
+        SolarTimes, Trigger, TriggerSource, is_scheduled, previous_scheduled,
+    };
     fn test_date_sunrise() -> DateTime<Utc> {
         DateTime::from_timestamp(1752414761, 0).unwrap()
     }
@@ -303,6 +866,15 @@ mod test {
     fn test_date_sunset() -> DateTime<Utc> {
         DateTime::from_timestamp(1752414761 + 6 * 60 * 60, 0).unwrap()
     }
+
+    /// The event following `trigger` without actions at solar noon or solar midnight
+    fn next_crossing(trigger: ActionTrigger) -> ActionTrigger {
+        let mut next = trigger.next();
+        while !is_scheduled(next, &[]) {
+            next = next.next();
+        }
+        next
+    }
     fn test_date_00() -> DateTime<Utc> {
         DateTime::from_timestamp(1752357600, 0).unwrap()
     }
@@ -310,6 +882,222 @@ mod test {
     fn test_date_23_59_59() -> DateTime<Utc> {
         DateTime::from_timestamp(1752443999, 0).unwrap()
     }
+    #[test]
+    fn action_trigger_parses_case_insensitive() {
+        let lower: ActionTrigger = serde_json::from_str("\"sunset\"").unwrap();
+        let upper: ActionTrigger = serde_json::from_str("\"SUNSET\"").unwrap();
+        let alias: ActionTrigger = serde_json::from_str("\"nightfall\"").unwrap();
+
+        assert_eq!(lower, ActionTrigger::Sunset);
+        assert_eq!(upper, ActionTrigger::Sunset);
+        assert_eq!(alias, ActionTrigger::Dusk);
+    }
+
+    #[test]
+    fn action_trigger_round_trips_through_its_name() {
+        let mut trigger = ActionTrigger::Dawn;
+        for _ in 0..6 {
+            let serialized = serde_json::to_string(&trigger).unwrap();
+
+            assert_eq!(serialized, format!("\"{trigger}\""));
+            assert_eq!(
+                serde_json::from_str::<ActionTrigger>(&serialized).unwrap(),
+                trigger
+            );
+            assert_eq!(
+                trigger.to_string().parse::<ActionTrigger>().unwrap(),
+                trigger
+            );
+            trigger = trigger.next();
+        }
+        assert_eq!(ActionTrigger::SolarNoon.to_string(), "solar_noon");
+    }
+
+    #[test]
+    fn event_cache_fires_event_once_reached() {
+        let at = test_date_sunset();
+        let event = |at, trigger| PendingEvent {
+            trigger,
+            at,
+            has_commands: true,
+        };
+        let mut cache = EventCache::new();
+
+        let before = cache.should_trigger(
+            at - TimeDelta::seconds(1),
+            Some(event(at, ActionTrigger::Sunset)),
+        );
+        // once reached, the scheduler already reports the following event
+        let reached = cache.should_trigger(
+            at,
+            Some(event(at + TimeDelta::hours(1), ActionTrigger::Dusk)),
+        );
+        let again = cache.should_trigger(
+            at + TimeDelta::seconds(1),
+            Some(event(at + TimeDelta::hours(1), ActionTrigger::Dusk)),
+        );
+
+        assert_eq!(before, None);
+        assert_eq!(reached, Some((ActionTrigger::Sunset, at)));
+        assert_eq!(again, None);
+    }
+
+    #[test]
+    fn pending_event_matches_next_event() {
+        let location = LocationInfo::new(Coordinates::new(49.598121, 11.003653).unwrap());
+        let actions: Actions = toml_edit::de::from_str("on_sunset = \"true\"").unwrap();
+        let scheduler = Scheduler::automatic(location, actions);
+
+        for date in [test_date_sunrise(), test_date_sunset()] {
+            let next = scheduler.next_event_at(date).unwrap();
+
+            let pending = scheduler.next_pending_at(date).unwrap();
+
+            assert_eq!((pending.trigger, pending.at), (next.trigger, next.at));
+            assert_eq!(pending.has_commands, next.action.is_some());
+        }
+    }
+
+    #[test]
+    fn clock_jump_across_sunset_catches_up() {
+        let at = test_date_sunset();
+        let location = LocationInfo::new(Coordinates::new(49.598121, 11.003653).unwrap());
+        let actions: Actions = toml_edit::de::from_str("on_sunset = \"true\"").unwrap();
+        let scheduler = Scheduler::automatic(location, actions);
+        let sunset = scheduler.next_event_at(at - TimeDelta::hours(1)).unwrap();
+        let mut cache = EventCache::new();
+        scheduler.should_trigger(sunset.at - TimeDelta::minutes(10), &mut cache);
+
+        // suspended through sunset, the clock jumped two hours ahead
+        let resumed = sunset.at + TimeDelta::hours(2);
+        let missed = cache.missed(resumed);
+
+        assert_eq!(missed, Some((ActionTrigger::Sunset, sunset.at)));
+        assert_eq!(cache.missed(resumed), None);
+        assert_eq!(scheduler.should_trigger(resumed, &mut cache), None);
+    }
+
+    #[test]
+    fn upcoming_events_follow_each_other() {
+        let location = LocationInfo::new(Coordinates::new(49.598121, 11.003653).unwrap());
+        let scheduler = Scheduler::automatic(location, Actions::default());
+
+        let events = scheduler.upcoming_events(test_date_sunrise(), 5);
+
+        let triggers = events.iter().map(|e| e.trigger).collect::<Vec<_>>();
+        assert_eq!(
+            triggers,
+            [
+                ActionTrigger::Sunset,
+                ActionTrigger::Dusk,
+                ActionTrigger::Dawn,
+                ActionTrigger::Sunrise,
+                ActionTrigger::Sunset
+            ]
+        );
+        assert!(events.windows(2).all(|w| w[0].at < w[1].at));
+    }
+
+    #[test]
+    fn events_between_covers_two_days() {
+        let location = LocationInfo::new(Coordinates::new(49.598121, 11.003653).unwrap());
+        let scheduler = Scheduler::automatic(location, Actions::default());
+        let start = test_date_00();
+        let end = start + TimeDelta::hours(48);
+
+        let events = scheduler.events_between(start, end);
+
+        assert_eq!(events.len(), 8, "{events:?}");
+        assert!(events.iter().all(|e| e.at >= start && e.at < end));
+        assert!(
+            events
+                .windows(2)
+                .all(|w| w[0].at < w[1].at && w[1].trigger == next_crossing(w[0].trigger))
+        );
+        assert!(scheduler.events_between(end, start).is_empty());
+    }
+
+    #[test]
+    fn events_between_ends_during_polar_day() {
+        let midsummer = DateTime::from_timestamp(1750507200, 0).unwrap(); // 2025-06-21 12:00 UTC
+        let scheduler = Scheduler::automatic(svalbard(), Actions::default());
+
+        let events = scheduler.events_between(midsummer, midsummer + TimeDelta::hours(48));
+
+        assert!(events.is_empty(), "{events:?}");
+    }
+
+    #[test]
+    fn events_between_repeats_manual_time_stamps() {
+        let day = NaiveDate::from_ymd_opt(2025, 7, 13).unwrap();
+        let scheduler = Scheduler::manual(
+            manual(&[
+                ("07:00:00", ActionTrigger::Sunrise),
+                ("21:00:00", ActionTrigger::Sunset),
+            ]),
+            Actions::default(),
+        );
+        let start = local(day, "00:00:00");
+
+        let events = scheduler.events_between(start, start + TimeDelta::hours(48));
+
+        let times = events.iter().map(|e| e.at).collect::<Vec<_>>();
+        let next_day = day.succ_opt().unwrap();
+        assert_eq!(
+            times,
+            [
+                local(day, "07:00:00"),
+                local(day, "21:00:00"),
+                local(next_day, "07:00:00"),
+                local(next_day, "21:00:00")
+            ]
+        );
+    }
+
+    #[test]
+    fn next_event_cycles_through_a_full_day() {
+        let location = LocationInfo::new(Coordinates::new(49.598121, 11.003653).unwrap());
+        let scheduler = Scheduler::automatic(location, Actions::default());
+        let start = test_date_00();
+        let mut events = vec![scheduler.next_event_at(start).unwrap()];
+
+        for minute in 1..=(26 * 60) {
+            let event = scheduler
+                .next_event_at(start + TimeDelta::minutes(minute))
+                .unwrap();
+            let last = events.last().unwrap();
+            if event != *last {
+                assert_eq!(
+                    event.trigger,
+                    next_crossing(last.trigger),
+                    "skipped after {last:?}"
+                );
+                assert!(event.at > last.at, "{event:?} is not after {last:?}");
+                events.push(event);
+            }
+        }
+
+        let triggers = events.iter().map(|e| e.trigger).collect::<Vec<_>>();
+        assert_eq!(
+            triggers,
+            [
+                ActionTrigger::Dawn,
+                ActionTrigger::Sunrise,
+                ActionTrigger::Sunset,
+                ActionTrigger::Dusk,
+                ActionTrigger::Dawn
+            ]
+        );
+    }
+
+    #[test]
+    fn action_trigger_rejects_unknown() {
+        let err = serde_json::from_str::<ActionTrigger>("\"noon\"").unwrap_err();
+
+        assert!(err.to_string().contains("Invalid action: noon"));
+        assert!(err.to_string().contains("solar_noon or solar_midnight"));
+    }
+
     #[test]
     fn interval_at_sunrise_works() {
         let coords = Coordinates::new(49.598121, 11.003653).unwrap();
@@ -319,6 +1107,49 @@ mod test {
         assert_eq!(interval.current_event(), ActionTrigger::Sunrise)
     }
 
+    #[test]
+    fn interval_around_solar_noon() {
+        let location = LocationInfo::new(Coordinates::new(49.598121, 11.003653).unwrap())
+            .with_extra_events([ActionTrigger::SolarNoon]);
+        let morning = test_date_sunrise() - TimeDelta::hours(4);
+
+        let noon = location.next_action_at(morning).unwrap();
+        let before = location
+            .interval_at(noon.1 - TimeDelta::minutes(1))
+            .unwrap();
+        let after = location
+            .interval_at(noon.1 + TimeDelta::minutes(1))
+            .unwrap();
+
+        assert_eq!(noon.0, ActionTrigger::SolarNoon);
+        let noon = noon.1;
+        // 11° east and the equation of time put July's solar noon at about 11:20 UTC
+        assert!(noon.time() > "11:00:00".parse().unwrap());
+        assert!(noon.time() < "11:40:00".parse().unwrap());
+        assert_eq!(before.current_event(), ActionTrigger::Sunrise);
+        assert_eq!(after.current_event(), ActionTrigger::SolarNoon);
+        assert_eq!(after.next, ActionTrigger::Sunset);
+    }
+
+    #[test]
+    fn solar_noon_is_only_scheduled_with_an_action() {
+        let location = "[automatic]\nlatitude = 49.598121\nlongitude = 11.003653\n";
+        let morning = test_date_sunrise() - TimeDelta::hours(4);
+        let next = |toml: &str| {
+            TriggerSource::from_config(&Configuration::parse(toml))
+                .unwrap()
+                .next_event_at(morning)
+                .unwrap()
+                .trigger
+        };
+
+        let without = next(location);
+        let with = next(&format!("{location}[actions]\non_solar_noon = \"true\"\n"));
+
+        assert_eq!(without, ActionTrigger::Sunset);
+        assert_eq!(with, ActionTrigger::SolarNoon);
+    }
+
     #[test]
     fn interval_at_dusk_works() {
         let coords = Coordinates::new(49.598121, 11.003653).unwrap();
@@ -363,4 +1194,487 @@ mod test {
 
         assert_eq!(interval.current_event(), ActionTrigger::Dusk)
     }
+
+    fn svalbard() -> LocationInfo {
+        LocationInfo::new(Coordinates::new(78.0, 15.0).unwrap())
+    }
+
+    #[test]
+    fn solar_times_are_computed_once_per_date() {
+        let location = LocationInfo::new(Coordinates::new(49.598121, 11.003653).unwrap());
+        let start = test_date_00();
+
+        location.interval_at(start).unwrap();
+        let first = location.solar_cache.computed.get();
+        for minute in 1..=60 {
+            location
+                .interval_at(start + TimeDelta::minutes(minute))
+                .unwrap();
+        }
+
+        assert_eq!(location.solar_cache.computed.get(), first);
+    }
+
+    #[test]
+    fn solar_cache_drops_past_dates() {
+        let location = LocationInfo::new(Coordinates::new(49.598121, 11.003653).unwrap());
+        let start = test_date_00();
+
+        location.interval_at(start).unwrap();
+        location.interval_at(start + TimeDelta::days(5)).unwrap();
+
+        let cutoff = (start + TimeDelta::days(3)).date_naive();
+        let times = location.solar_cache.times.borrow();
+        assert!(times.keys().all(|day| *day >= cutoff), "{times:?}");
+    }
+
+    #[test]
+    fn polar_day_waits_until_the_sun_sets_again() {
+        let midsummer = DateTime::from_timestamp(1750507200, 0).unwrap(); // 2025-06-21 12:00 UTC
+        let scheduler = Scheduler::automatic(svalbard(), Actions::default());
+
+        let interval = svalbard().interval_at(midsummer).unwrap();
+        let next = scheduler.next_event_at(midsummer).unwrap();
+
+        assert_eq!(interval.current_event(), ActionTrigger::Sunrise);
+        assert_eq!(next.polar, Some(Polar::Day));
+        // polar day ends late in August, around midnight
+        assert_eq!(next.at.month(), 8, "{next:?}");
+    }
+
+    #[test]
+    fn fallback_stands_in_for_missing_sunset() {
+        let midsummer = NaiveDate::from_ymd_opt(2025, 6, 21).unwrap();
+        let sunset = NaiveTime::from_hms_opt(21, 0, 0).unwrap();
+        let location = svalbard().with_fallback(Fallback {
+            sunset: Some(sunset),
+            ..Fallback::default()
+        });
+
+        let times = location.solar_times(midsummer);
+
+        let expected = midsummer
+            .and_time(sunset)
+            .and_local_timezone(Local)
+            .unwrap()
+            .with_timezone(&Utc);
+        assert_eq!(times.sunset, expected);
+        assert!(!times.missing(midsummer).contains(&ActionTrigger::Sunset));
+        assert!(times.missing(midsummer).contains(&ActionTrigger::Sunrise));
+    }
+
+    #[test]
+    fn fallback_skipped_by_the_clock_is_an_hour_later() {
+        let dst_starts = NaiveDate::from_ymd_opt(2025, 3, 30).unwrap();
+        let never = DateTime::<Utc>::MIN_UTC;
+        let times = SolarTimes {
+            dawn: never,
+            sunrise: never,
+            sunset: never,
+            dusk: never,
+        };
+        let fallback = Fallback {
+            sunrise: NaiveTime::from_hms_opt(2, 30, 0),
+            ..Fallback::default()
+        };
+
+        let times = times.with_fallback(dst_starts, &fallback, &chrono_tz::Europe::Berlin);
+
+        // 03:30 CEST, Berlin's clock jumps from 02:00 to 03:00
+        assert_eq!(times.sunrise.to_rfc3339(), "2025-03-30T01:30:00+00:00");
+    }
+
+    #[test]
+    fn polar_night_waits_for_the_first_dawn() {
+        let midwinter = DateTime::from_timestamp(1766318400, 0).unwrap(); // 2025-12-21 12:00 UTC
+        let scheduler = Scheduler::automatic(svalbard(), Actions::default());
+
+        let interval = svalbard().interval_at(midwinter).unwrap();
+        let next = scheduler.next_event_at(midwinter).unwrap();
+
+        assert_eq!(interval.current_event(), ActionTrigger::Dusk);
+        assert_eq!(next.trigger, ActionTrigger::Dawn);
+        assert_eq!(next.polar, Some(Polar::Night));
+        assert_eq!(next.at.month(), 1, "{next:?}");
+    }
+
+    #[test]
+    fn polar_day_is_told_by_the_noon_sun_before_the_equinox() {
+        // the sun skims along the horizon at the pole in the days before the March equinox
+        let date = NaiveDate::from_ymd_opt(2025, 3, 19).unwrap();
+        let pole = Coordinates::new(89.9, 15.0).unwrap();
+
+        let times = SolarTimes::compute(pole, date, Twilight::default());
+
+        assert_eq!(times.polar(date, pole.lat()), Some(Polar::Day));
+    }
+
+    #[test]
+    fn mid_latitudes_are_not_polar() {
+        let scheduler = Scheduler::automatic(
+            LocationInfo::new(Coordinates::new(49.598121, 11.003653).unwrap()),
+            Actions::default(),
+        );
+
+        let next = scheduler.next_event_at(test_date_sunrise()).unwrap();
+
+        assert_eq!(next.polar, None);
+    }
+
+    fn manual(times: &[(&str, ActionTrigger)]) -> ManualConfig {
+        ManualConfig {
+            time_stamps: times
+                .iter()
+                .map(|(time, action)| ManualTimeStamp {
+                    trigger_time: time.parse().unwrap(),
+                    action: *action,
+                    name: None,
+                    command: None,
+                    days: vec![],
+                })
+                .collect(),
+            timezone: None,
+        }
+    }
+
+    fn local(date: NaiveDate, time: &str) -> DateTime<Utc> {
+        date.and_time(time.parse().unwrap())
+            .and_local_timezone(Local)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn manual_picks_next_not_closest_time_stamp() {
+        let day = NaiveDate::from_ymd_opt(2025, 7, 13).unwrap();
+        let times = manual(&[
+            ("07:00:00", ActionTrigger::Sunrise),
+            ("21:00:00", ActionTrigger::Sunset),
+        ]);
+
+        let next = times.next_action_at(local(day, "07:30:00"));
+
+        assert_eq!(next, Some((ActionTrigger::Sunset, local(day, "21:00:00"))));
+    }
+
+    #[test]
+    fn manual_wraps_to_tomorrow_once_all_passed() {
+        let day = NaiveDate::from_ymd_opt(2025, 7, 13).unwrap();
+        let tomorrow = day.succ_opt().unwrap();
+        let times = manual(&[
+            ("21:00:00", ActionTrigger::Sunset),
+            ("07:00:00", ActionTrigger::Sunrise),
+        ]);
+
+        let at_last = times.next_action_at(local(day, "21:00:00"));
+        let after_last = times.next_action_at(local(day, "22:00:00"));
+
+        assert_eq!(
+            at_last,
+            Some((ActionTrigger::Sunrise, local(tomorrow, "07:00:00")))
+        );
+        assert_eq!(
+            after_last,
+            Some((ActionTrigger::Sunrise, local(tomorrow, "07:00:00")))
+        );
+    }
+
+    #[test]
+    fn manual_wraps_around_midnight() {
+        let day = NaiveDate::from_ymd_opt(2025, 7, 13).unwrap();
+        let tomorrow = day.succ_opt().unwrap();
+        let times = manual(&[
+            ("23:30:00", ActionTrigger::Dusk),
+            ("00:15:00", ActionTrigger::Dawn),
+        ]);
+
+        let before_midnight = times.next_action_at(local(day, "23:45:00"));
+        let after_midnight = times.next_action_at(local(tomorrow, "00:05:00"));
+
+        assert_eq!(
+            before_midnight,
+            Some((ActionTrigger::Dawn, local(tomorrow, "00:15:00")))
+        );
+        assert_eq!(before_midnight, after_midnight);
+    }
+
+    #[test]
+    fn weekday_time_stamps_skip_the_weekend() {
+        let mut schedule = manual(&[
+            ("07:00:00", ActionTrigger::Sunrise),
+            ("21:00:00", ActionTrigger::Sunset),
+        ]);
+        for m in &mut schedule.time_stamps {
+            m.days = vec![
+                Weekday::Mon,
+                Weekday::Tue,
+                Weekday::Wed,
+                Weekday::Thu,
+                Weekday::Fri,
+            ];
+        }
+        let scheduler = Scheduler::manual(schedule, Actions::default());
+        let friday = NaiveDate::from_ymd_opt(2025, 7, 11).unwrap();
+        let sunday = NaiveDate::from_ymd_opt(2025, 7, 13).unwrap();
+        let monday = NaiveDate::from_ymd_opt(2025, 7, 14).unwrap();
+
+        let next = scheduler.next_event_at(local(friday, "22:00:00")).unwrap();
+        let current = scheduler
+            .current_event_at(local(sunday, "12:00:00"))
+            .unwrap();
+
+        assert_eq!(next.at, local(monday, "07:00:00"));
+        assert_eq!(next.trigger, ActionTrigger::Sunrise);
+        assert_eq!(current.trigger, ActionTrigger::Sunset);
+        assert_eq!(current.since, Some(local(friday, "21:00:00")));
+    }
+
+    #[test]
+    fn weekend_time_stamp_overrides_weekday_one() {
+        let mut schedule = manual(&[
+            ("07:00:00", ActionTrigger::Sunrise),
+            ("09:00:00", ActionTrigger::Sunrise),
+        ]);
+        schedule.time_stamps[0].days = vec![
+            Weekday::Mon,
+            Weekday::Tue,
+            Weekday::Wed,
+            Weekday::Thu,
+            Weekday::Fri,
+        ];
+        schedule.time_stamps[1].days = vec![Weekday::Sat, Weekday::Sun];
+        let scheduler = Scheduler::manual(schedule, Actions::default());
+        let friday = NaiveDate::from_ymd_opt(2025, 7, 11).unwrap();
+        let saturday = NaiveDate::from_ymd_opt(2025, 7, 12).unwrap();
+
+        let events =
+            scheduler.events_between(local(friday, "00:00:00"), local(saturday, "23:59:59"));
+
+        let times = events.iter().map(|e| e.at).collect::<Vec<_>>();
+        assert_eq!(
+            times,
+            [local(friday, "07:00:00"), local(saturday, "09:00:00")]
+        );
+    }
+
+    #[test]
+    fn timezone_moves_time_stamps_across_dst() {
+        let mut schedule = manual(&[("07:00:00", ActionTrigger::Sunrise)]);
+        schedule.timezone = Some(chrono_tz::Europe::Berlin);
+        let utc = |s: &str| s.parse::<DateTime<Utc>>().unwrap();
+
+        let winter = schedule.next_action_at(utc("2025-03-29T12:00:00Z"));
+        let summer = schedule.next_action_at(utc("2025-03-30T12:00:00Z"));
+        let current = schedule.current_action_at(utc("2025-10-26T12:00:00Z"));
+
+        assert_eq!(
+            winter,
+            Some((ActionTrigger::Sunrise, utc("2025-03-30T05:00:00Z")))
+        );
+        assert_eq!(
+            summer,
+            Some((ActionTrigger::Sunrise, utc("2025-03-31T05:00:00Z")))
+        );
+        assert_eq!(current.unwrap().since, Some(utc("2025-10-26T06:00:00Z")));
+    }
+
+    #[test]
+    fn time_stamp_in_spring_forward_gap_is_skipped() {
+        let mut schedule = manual(&[
+            ("02:30:00", ActionTrigger::Dawn),
+            ("21:00:00", ActionTrigger::Sunset),
+        ]);
+        schedule.timezone = Some(chrono_tz::Europe::Berlin);
+        let utc = |s: &str| s.parse::<DateTime<Utc>>().unwrap();
+
+        // 01:00 CET, the clock jumps from 02:00 to 03:00 an hour later
+        let next = schedule.next_action_at(utc("2025-03-30T00:00:00Z"));
+        let current = schedule.current_action_at(utc("2025-03-30T12:00:00Z"));
+
+        assert_eq!(
+            next,
+            Some((ActionTrigger::Sunset, utc("2025-03-30T19:00:00Z")))
+        );
+        assert_eq!(current.unwrap().since, Some(utc("2025-03-29T20:00:00Z")));
+    }
+
+    #[test]
+    fn repeated_time_stamp_fires_once_when_falling_back() {
+        let mut schedule = manual(&[
+            ("02:30:00", ActionTrigger::Dawn),
+            ("21:00:00", ActionTrigger::Sunset),
+        ]);
+        schedule.timezone = Some(chrono_tz::Europe::Berlin);
+        let utc = |s: &str| s.parse::<DateTime<Utc>>().unwrap();
+
+        let first = schedule.next_action_at(utc("2025-10-26T00:00:00Z"));
+        // 02:45 CEST, the clock shows 02:30 again in 45 minutes
+        let after_first = schedule.next_action_at(utc("2025-10-26T00:45:00Z"));
+        let current = schedule.current_action_at(utc("2025-10-26T01:45:00Z"));
+
+        assert_eq!(
+            first,
+            Some((ActionTrigger::Dawn, utc("2025-10-26T00:30:00Z")))
+        );
+        assert_eq!(
+            after_first,
+            Some((ActionTrigger::Sunset, utc("2025-10-26T20:00:00Z")))
+        );
+        assert_eq!(current.unwrap().since, Some(utc("2025-10-26T00:30:00Z")));
+    }
+
+    #[test]
+    fn manual_without_time_stamps_has_no_event() {
+        assert_eq!(manual(&[]).next_action_at(test_date_00()), None);
+        assert!(manual(&[]).current_action_at(test_date_00()).is_none());
+    }
+
+    #[test]
+    fn manual_current_is_last_passed_time_stamp() {
+        let day = NaiveDate::from_ymd_opt(2025, 7, 13).unwrap();
+        let times = manual(&[
+            ("07:00:00", ActionTrigger::Sunrise),
+            ("21:00:00", ActionTrigger::Sunset),
+        ]);
+
+        assert_eq!(
+            times.current_action_at(local(day, "06:00:00")),
+            Some(CurrentEvent {
+                trigger: ActionTrigger::Sunset,
+                since: Some(local(day.pred_opt().unwrap(), "21:00:00"))
+            })
+        );
+        assert_eq!(
+            times.current_action_at(local(day, "07:00:00")),
+            Some(CurrentEvent {
+                trigger: ActionTrigger::Sunrise,
+                since: Some(local(day, "07:00:00"))
+            })
+        );
+        assert_eq!(
+            times
+                .current_action_at(local(day, "20:59:59"))
+                .map(|current| current.trigger),
+            Some(ActionTrigger::Sunrise)
+        );
+    }
+
+    #[test]
+    fn named_time_stamps_run_their_own_commands() {
+        let mut schedule = manual(&[
+            ("07:00:00", ActionTrigger::Sunrise),
+            ("22:00:00", ActionTrigger::Dusk),
+        ]);
+        schedule.time_stamps[1].name = Some("night_mode".to_string());
+        let actions: Actions = toml_edit::de::from_str(concat!(
+            "on_sunrise = \"day\"\n",
+            "on_dusk = \"dusk\"\n",
+            "[named]\n",
+            "night_mode = \"dim\"\n",
+        ))
+        .unwrap();
+        let scheduler = Scheduler::manual(schedule, actions);
+        let day = NaiveDate::from_ymd_opt(2025, 7, 13).unwrap();
+
+        let morning = scheduler.next_event_at(local(day, "06:00:00")).unwrap();
+        let evening = scheduler.next_event_at(local(day, "21:00:00")).unwrap();
+
+        assert_eq!(morning.action.as_deref(), Some("day"));
+        assert_eq!(evening.trigger, ActionTrigger::Dusk);
+        assert_eq!(evening.action.as_deref(), Some("dim"));
+        assert_eq!(
+            scheduler.commands_at(evening.trigger, evening.at)[0].command(),
+            "dim"
+        );
+    }
+
+    #[test]
+    fn inline_commands_override_actions() {
+        let mut schedule = manual(&[("23:30:00", ActionTrigger::Dusk)]);
+        schedule.time_stamps[0].command = Some(ActionCommands::One(ActionCommand::Command(
+            "hyprctl dispatch dpms off".to_string(),
+        )));
+        let actions: Actions = toml_edit::de::from_str("on_dusk = \"dusk\"").unwrap();
+        let scheduler = Scheduler::manual(schedule, actions);
+        let day = NaiveDate::from_ymd_opt(2025, 7, 13).unwrap();
+
+        let event = scheduler.next_event_at(local(day, "21:00:00")).unwrap();
+
+        assert_eq!(event.action.as_deref(), Some("hyprctl dispatch dpms off"));
+    }
+
+    #[test]
+    fn negative_offset_fires_earlier() {
+        let coords = Coordinates::new(49.598121, 11.003653).unwrap();
+        let offsets = Offsets {
+            sunset: -15,
+            ..Default::default()
+        };
+        let plain = Scheduler::automatic(LocationInfo::new(coords), Actions::default());
+        let early = Scheduler::automatic(
+            LocationInfo::new(coords).with_offsets(offsets),
+            Actions::default(),
+        );
+
+        let sunset = plain.next_event_at(test_date_sunrise()).unwrap();
+        let shifted = early.next_event_at(test_date_sunrise()).unwrap();
+
+        assert_eq!(shifted.trigger, ActionTrigger::Sunset);
+        assert_eq!(sunset.at - shifted.at, TimeDelta::minutes(15));
+        // the sunset period starts with the shifted event as well
+        let current = early
+            .current_event_at(shifted.at + TimeDelta::minutes(1))
+            .unwrap();
+        assert_eq!(current.trigger, ActionTrigger::Sunset);
+    }
+
+    #[test]
+    fn berlin_times_are_plausible() {
+        let config = Configuration {
+            automatic: Some(AutomaticConfig {
+                name: None,
+                longitude: Some(13.405),
+                latitude: Some(52.52),
+                location: None,
+                source: Default::default(),
+                event_cache: None,
+                twilight: Default::default(),
+                offsets: Default::default(),
+                fallback: Default::default(),
+                profiles: Default::default(),
+                active_profile: None,
+            }),
+            ..Default::default()
+        };
+        let source = TriggerSource::from_config(&config).unwrap();
+        let at = |time: &str| {
+            format!("2025-06-21T{time}Z")
+                .parse::<DateTime<Utc>>()
+                .unwrap()
+        };
+
+        // Swapping latitude and longitude moves sunset to early afternoon
+        let sunrise = source.next_event_at(at("02:30:00")).unwrap();
+        let sunset = source.next_event_at(at("12:00:00")).unwrap();
+
+        assert_eq!(sunrise.trigger, ActionTrigger::Sunrise);
+        assert!((at("02:30:00")..at("03:00:00")).contains(&sunrise.at));
+        assert_eq!(sunset.trigger, ActionTrigger::Sunset);
+        assert!((at("19:15:00")..at("19:50:00")).contains(&sunset.at));
+    }
+
+    #[test]
+    fn current_event_precedes_next_event() {
+        let location = LocationInfo::new(Coordinates::new(49.598121, 11.003653).unwrap());
+        let scheduler = Scheduler::automatic(location, Actions::default());
+
+        for date in [test_date_sunrise(), test_date_dusk(), test_date_00()] {
+            let next = scheduler.next_event_at(date).unwrap();
+
+            let current = scheduler.current_event_at(date).unwrap();
+
+            assert_eq!(current.trigger, previous_scheduled(next.trigger, &[]));
+            assert!(current.since.unwrap() <= date);
+        }
+    }
 }
@@ -1,24 +1,145 @@
-use crate::{config::Configuration, context::Context, daemon::Daemon, info::InfoGatherer};
+use std::process::ExitCode;
+
+use chrono::Utc;
+
+use crate::{
+    actions::{Action, Response},
+    bench::Benchmark,
+    client::{self, DaemonStatus, daemon_status},
+    config::Configuration,
+    confirm,
+    context::Context,
+    daemon::{self, Daemon},
+    doctor::Doctor,
+    dump_schedule::ScheduleDump,
+    error::Error,
+    info::InfoGatherer,
+    lint::Linter,
+    pid_file,
+    precompute::Precomputer,
+    scheduler::{EventSource, TriggerSource},
+    test_action::ActionTester,
+    upcoming::Upcoming,
+    validate::Validator,
+};
+
+/// Exit code of `status` for a stopped daemon, as for LSB init scripts
+const STATUS_STOPPED: u8 = 3;
 
 pub enum AppState {
     Daemon(Daemon),
+    /// Run the actions of the current event without starting the daemon
+    Once,
     Info(InfoGatherer),
-    DefaultConfig,
+    DefaultConfig {
+        out: Option<String>,
+        yes: bool,
+    },
+    Init {
+        force: bool,
+    },
+    Doctor(Doctor),
+    Validate(Validator),
+    Lint(Linter),
+    Precompute(Precomputer),
+    Bench(Benchmark),
+    Upcoming(Upcoming),
+    DumpSchedule(ScheduleDump),
+    TestAction(ActionTester),
+    IsRunning,
+    Status,
+    IsDark,
+    /// Forward an action to the running daemon
+    Send(Action),
 }
 
 impl AppState {
-    pub fn run(self, context: Context) -> crate::error::Result<()> {
+    pub fn run(self, context: Context) -> crate::error::Result<ExitCode> {
         match self {
             AppState::Daemon(daemon) => daemon.run(context)?,
+            AppState::Once => daemon::run_once(context)?,
             AppState::Info(info) => info.print(context)?,
-            AppState::DefaultConfig => {
+            AppState::Doctor(doctor) => doctor.run(context)?,
+            AppState::Lint(linter) => linter.run(context)?,
+            AppState::Precompute(precomputer) => precomputer.run(context)?,
+            AppState::Bench(bench) => bench.run(context)?,
+            AppState::Upcoming(upcoming) => upcoming.run(context)?,
+            AppState::DumpSchedule(dump) => dump.run(context)?,
+            AppState::TestAction(tester) => return tester.run(context),
+            AppState::Validate(validator) => return validator.run(context),
+            AppState::DefaultConfig { out: None, .. } => {
+                print!("{}", Configuration::default_document()?)
+            }
+            AppState::DefaultConfig {
+                out: Some(path),
+                yes,
+            } => {
+                confirm::overwrite(&path, yes)?;
+                std::fs::write(&path, Configuration::default_document()?.to_string())?;
+                println!("Wrote the default config to {path}");
+            }
+            AppState::Init { force } => {
+                let path = &context.config_path;
+                if !force && std::path::Path::new(path).exists() {
+                    return Err(Error::NotConfirmed(format!(
+                        "{path} already exists (pass --force to replace it)"
+                    )));
+                }
+                if let Some(dir) = std::path::Path::new(path).parent() {
+                    std::fs::create_dir_all(dir)?;
+                }
+                std::fs::write(path, Configuration::default_document()?.to_string())?;
+                println!("Wrote the default config to {path}");
                 println!(
-                    "{}",
-                    toml_edit::ser::to_string_pretty(&Configuration::default())?
-                )
+                    "Set latitude and longitude in [automatic] to fire actions at your location's sunrise and sunset"
+                );
+            }
+            AppState::Send(action) => match client::send(action)? {
+                Response::Done => {}
+                response @ (Response::ConfigValid | Response::Enabled(_) | Response::Info(_)) => {
+                    println!("{response}")
+                }
+                response @ (Response::ConfigInvalid(_) | Response::Error(_)) => {
+                    eprintln!("{response}");
+                    return Ok(ExitCode::FAILURE);
+                }
+            },
+            AppState::IsRunning => {
+                let status = daemon_status();
+                println!("{status}");
+                if status != DaemonStatus::Running {
+                    return Ok(ExitCode::FAILURE);
+                }
+            }
+            AppState::Status => match daemon_status() {
+                DaemonStatus::Running => match pid_file::read(&context.config.pid_file) {
+                    Some(pid) => println!("running, pid {pid}"),
+                    None => println!("running, pid unknown"),
+                },
+                DaemonStatus::Stale => {
+                    println!("stopped, stale socket");
+                    return Ok(ExitCode::from(STATUS_STOPPED));
+                }
+                DaemonStatus::NotRunning => {
+                    println!("stopped");
+                    return Ok(ExitCode::from(STATUS_STOPPED));
+                }
+            },
+            AppState::IsDark => {
+                let current = TriggerSource::from_config(&context.config)?
+                    .current_event_at(Utc::now())
+                    .ok_or(Error::InvalidConfiguration(
+                        "no events are scheduled".to_string(),
+                    ))?
+                    .trigger;
+                let dark = context.config.is_dark(current);
+                println!("{}", if dark { "dark" } else { "light" });
+                if !dark {
+                    return Ok(ExitCode::FAILURE);
+                }
             }
         }
 
-        Ok(())
+        Ok(ExitCode::SUCCESS)
     }
 }
@@ -0,0 +1,140 @@
+use std::{ffi::OsStr, io, time::Duration};
+
+#[cfg(unix)]
+use std::os::fd::{FromRawFd, OwnedFd, RawFd};
+
+/// The first descriptor passed by socket activation, see sd_listen_fds(3)
+#[cfg(unix)]
+const LISTEN_FDS_START: RawFd = 3;
+
+/// Sends `state` to systemd, e.g. `READY=1`. Does nothing unless run by a `Type=notify` service.
+pub fn notify(state: &str) -> io::Result<()> {
+    match std::env::var_os("NOTIFY_SOCKET") {
+        Some(socket) => send(&socket, state),
+        None => Ok(()),
+    }
+}
+
+/// How often to send `WATCHDOG=1`, half the timeout of the service, `None` without a watchdog
+pub fn watchdog_interval() -> Option<Duration> {
+    watchdog_interval_from(
+        std::env::var("WATCHDOG_USEC").ok().as_deref(),
+        std::env::var("WATCHDOG_PID").ok().as_deref(),
+    )
+}
+
+/// The socket passed by a socket unit, `None` unless systemd started the daemon on a connection
+#[cfg(unix)]
+pub fn listen_fd() -> io::Result<Option<OwnedFd>> {
+    let fds = listen_fds_from(
+        std::env::var("LISTEN_FDS").ok().as_deref(),
+        std::env::var("LISTEN_PID").ok().as_deref(),
+    );
+    if fds == 0 {
+        return Ok(None);
+    }
+    // actions would otherwise be told about descriptors they don't have, see sd_listen_fds(3)
+    for var in ["LISTEN_FDS", "LISTEN_PID", "LISTEN_FDNAMES"] {
+        // SAFETY: the daemon binds its socket before it starts any other thread
+        unsafe { std::env::remove_var(var) };
+    }
+    // SAFETY: systemd passes the descriptors from 3 on to the process named by LISTEN_PID, no
+    // one else in the process owns them
+    let fd = unsafe { OwnedFd::from_raw_fd(LISTEN_FDS_START) };
+    // actions shouldn't inherit the socket
+    nix::fcntl::fcntl(
+        &fd,
+        nix::fcntl::FcntlArg::F_SETFD(nix::fcntl::FdFlag::FD_CLOEXEC),
+    )?;
+    Ok(Some(fd))
+}
+
+fn listen_fds_from(fds: Option<&str>, pid: Option<&str>) -> usize {
+    // inherited from a parent that was socket activated itself
+    if pid.and_then(|pid| pid.parse().ok()) != Some(std::process::id()) {
+        return 0;
+    }
+    fds.and_then(|fds| fds.parse().ok()).unwrap_or(0)
+}
+
+fn watchdog_interval_from(usec: Option<&str>, pid: Option<&str>) -> Option<Duration> {
+    // the watchdog may be meant for another process of the service
+    if let Some(pid) = pid
+        && pid.parse() != Ok(std::process::id())
+    {
+        return None;
+    }
+    let usec: u64 = usec?.parse().ok()?;
+    (usec > 0).then(|| Duration::from_micros(usec / 2))
+}
+
+#[cfg(unix)]
+fn send(socket: &OsStr, state: &str) -> io::Result<()> {
+    use std::os::unix::{ffi::OsStrExt, net::UnixDatagram};
+
+    let datagram = UnixDatagram::unbound()?;
+    match socket.as_bytes().strip_prefix(b"@") {
+        #[cfg(target_os = "linux")]
+        Some(name) => {
+            use std::os::{linux::net::SocketAddrExt, unix::net::SocketAddr};
+            datagram.send_to_addr(state.as_bytes(), &SocketAddr::from_abstract_name(name)?)?
+        }
+        _ => datagram.send_to(state.as_bytes(), socket)?,
+    };
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn send(_socket: &OsStr, _state: &str) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::{listen_fds_from, send, watchdog_interval_from};
+
+    #[cfg(unix)]
+    #[test]
+    fn state_is_sent_to_notify_socket() {
+        use std::os::unix::net::UnixDatagram;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notify.sock");
+        let systemd = UnixDatagram::bind(&path).unwrap();
+
+        send(path.as_os_str(), "READY=1").unwrap();
+
+        let mut buf = [0; 16];
+        let len = systemd.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"READY=1");
+    }
+
+    #[test]
+    fn listen_fds_are_only_taken_for_own_pid() {
+        let own = std::process::id().to_string();
+
+        assert_eq!(listen_fds_from(Some("1"), Some(&own)), 1);
+        assert_eq!(listen_fds_from(Some("1"), Some("1")), 0);
+        assert_eq!(listen_fds_from(Some("1"), None), 0);
+        assert_eq!(listen_fds_from(None, Some(&own)), 0);
+    }
+
+    #[test]
+    fn watchdog_pings_twice_per_timeout() {
+        let own = std::process::id().to_string();
+
+        assert_eq!(
+            watchdog_interval_from(Some("30000000"), None),
+            Some(Duration::from_secs(15))
+        );
+        assert_eq!(
+            watchdog_interval_from(Some("30000000"), Some(&own)),
+            Some(Duration::from_secs(15))
+        );
+        assert_eq!(watchdog_interval_from(Some("30000000"), Some("1")), None);
+        assert_eq!(watchdog_interval_from(Some("0"), None), None);
+        assert_eq!(watchdog_interval_from(None, None), None);
+    }
+}
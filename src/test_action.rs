@@ -0,0 +1,85 @@
+use std::process::ExitCode;
+
+use chrono::Utc;
+
+use crate::{
+    context::Context,
+    outputs, runner,
+    scheduler::{ActionTrigger, EventSource, TriggerSource},
+};
+
+/// Runs the commands of a trigger right away, without the daemon
+pub struct ActionTester {
+    trigger: ActionTrigger,
+    dry_run: bool,
+}
+
+impl ActionTester {
+    pub fn new(trigger: ActionTrigger, dry_run: bool) -> Self {
+        Self { trigger, dry_run }
+    }
+
+    pub fn run(self, context: Context) -> crate::error::Result<ExitCode> {
+        let config = &context.config;
+        let actions = config.actions.get(self.trigger);
+        if actions.is_empty() {
+            return Err(crate::error::Error::InvalidAction(format!(
+                "{}, no command is configured for it",
+                self.trigger
+            )));
+        }
+        let next = TriggerSource::from_config(config)?.next_event_at(Utc::now());
+        let outputs = outputs::outputs(config);
+        let mut failed = false;
+        for action in actions {
+            println!("{}", action.command());
+            if self.dry_run {
+                continue;
+            }
+            let detach = action.detach();
+            for mut command in runner::commands(config, action.command(), &outputs, detach) {
+                runner::event_env(
+                    &mut command,
+                    self.trigger,
+                    Utc::now(),
+                    config.enabled,
+                    config.location_name(),
+                    next.as_ref(),
+                );
+                let status = command.status()?;
+                if detach {
+                    println!("started in the background");
+                } else {
+                    println!("exited with {status}");
+                }
+                failed |= !status.success();
+            }
+        }
+        Ok(if failed {
+            ExitCode::FAILURE
+        } else {
+            ExitCode::SUCCESS
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{config::Configuration, context::Context, scheduler::ActionTrigger};
+
+    use super::ActionTester;
+
+    #[test]
+    fn missing_command_is_an_invalid_action() {
+        let context = Context::create_from_config(Configuration::default(), String::new());
+
+        let err = ActionTester::new(ActionTrigger::Sunset, true)
+            .run(context)
+            .unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "Invalid action: sunset, no command is configured for it"
+        );
+    }
+}
@@ -0,0 +1,65 @@
+use chrono::{DateTime, Local, Utc};
+use serde::Serialize;
+
+use crate::{
+    context::Context,
+    info::EventInfo,
+    scheduler::{EventSource, TriggerSource},
+};
+
+/// Upper bound for the number of listed events, each one can take a search through the year at
+/// polar latitudes
+const MAX_COUNT: usize = 100;
+
+#[derive(Serialize)]
+struct UpcomingEvent {
+    #[serde(flatten)]
+    event: EventInfo,
+    seconds_remaining: i64,
+}
+
+pub struct Upcoming {
+    count: usize,
+    json: bool,
+    from: DateTime<Utc>,
+}
+
+impl Upcoming {
+    pub fn new(count: usize, json: bool, from: DateTime<Utc>) -> Self {
+        Self {
+            count: count.min(MAX_COUNT),
+            json,
+            from,
+        }
+    }
+
+    pub fn run(self, context: Context) -> crate::error::Result<()> {
+        let now = Utc::now();
+        let events = TriggerSource::from_config(&context.config)?
+            .upcoming_events(self.from, self.count)
+            .into_iter()
+            .map(|event| UpcomingEvent {
+                seconds_remaining: event.time_until(now).num_seconds(),
+                event,
+            })
+            .collect::<Vec<_>>();
+
+        if self.json {
+            println!("{}", serde_json::to_string_pretty(&events)?);
+            return Ok(());
+        }
+        if events.is_empty() {
+            println!("No pending event");
+        }
+        for UpcomingEvent { event, .. } in events {
+            println!(
+                "{:<8} {} {:<11} {}",
+                event.trigger.to_string(),
+                event.at.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S"),
+                event.countdown(now),
+                event.action.as_deref().unwrap_or("-")
+            );
+        }
+        Ok(())
+    }
+}
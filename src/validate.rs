@@ -0,0 +1,136 @@
+use std::{fmt::Display, process::ExitCode};
+
+use crate::{config::Configuration, context::Context, lint::lint_actions};
+
+/// Result of a single check of the config file
+#[derive(Debug, PartialEq, Eq)]
+pub enum Check {
+    Passed(String),
+    Warning(String),
+    Failed(String),
+}
+
+impl Display for Check {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Check::Passed(what) => f.write_str(&format!("ok       {what}")),
+            Check::Warning(what) => f.write_str(&format!("warning  {what}")),
+            Check::Failed(what) => f.write_str(&format!("FAIL     {what}")),
+        }
+    }
+}
+
+/// Runs the checks the daemon applies when loading or reloading the file at `path`, stopping
+/// at the first failure
+pub fn checks(path: &str) -> Vec<Check> {
+    let mut checks = vec![];
+    let config = match Configuration::load_existing(path) {
+        Ok(config) => config,
+        Err(err) => {
+            checks.push(Check::Failed(err.to_string()));
+            return checks;
+        }
+    };
+    checks.push(Check::Passed(format!("{path} loads")));
+    match config.validate() {
+        Ok(()) => checks.push(Check::Passed("events can be scheduled".to_string())),
+        Err(err) => {
+            checks.push(Check::Failed(err.to_string()));
+            return checks;
+        }
+    }
+    if config.actions.is_empty() {
+        checks.push(Check::Warning(
+            "no actions configured, events fire without running anything".to_string(),
+        ));
+    } else {
+        checks.push(Check::Passed("actions are configured".to_string()));
+    }
+    for finding in lint_actions(&config.actions) {
+        checks.push(Check::Warning(finding.to_string()));
+    }
+    checks
+}
+
+/// Checks the config file without starting the daemon, e.g. from a pre-commit hook
+pub struct Validator {
+    strict: bool,
+}
+
+impl Validator {
+    pub fn new(strict: bool) -> Self {
+        Self { strict }
+    }
+
+    pub fn run(self, context: Context) -> crate::error::Result<ExitCode> {
+        let checks = checks(&context.config_path);
+        for check in &checks {
+            println!("{check}");
+        }
+        if checks.iter().any(|check| match check {
+            Check::Failed(_) => true,
+            Check::Warning(_) => self.strict,
+            Check::Passed(_) => false,
+        }) {
+            println!("Config is invalid");
+            return Ok(ExitCode::FAILURE);
+        }
+        println!("Config is valid");
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Check, checks};
+
+    fn check_file(toml: &str) -> Vec<Check> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(&path, toml).unwrap();
+        checks(path.to_str().unwrap())
+    }
+
+    #[test]
+    fn valid_config_passes_every_check() {
+        let checks = check_file(
+            "[automatic]\nlatitude = 52.5\nlongitude = 13.4\n[actions]\non_sunset = \"true\"\n",
+        );
+
+        assert_eq!(checks.len(), 3);
+        assert!(checks.iter().all(|c| matches!(c, Check::Passed(_))));
+    }
+
+    #[test]
+    fn invalid_coordinates_fail() {
+        let checks = check_file("[automatic]\nlatitude = 91.0\nlongitude = 13.4\n");
+
+        assert_eq!(checks.len(), 2);
+        assert_eq!(
+            checks[1],
+            Check::Failed("Invalid Coordinates - lat: 91 long: 13.4".to_string())
+        );
+    }
+
+    #[test]
+    fn suspicious_actions_warn() {
+        let checks = check_file(
+            "[automatic]\nlatitude = 52.5\nlongitude = 13.4\n[actions]\non_sunset = \"rm -rf ~\"\n",
+        );
+
+        assert_eq!(
+            checks.last(),
+            Some(&Check::Warning(
+                "on_sunset: recursively removes the root or home directory".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn missing_actions_only_warn() {
+        let checks = check_file("[automatic]\nlatitude = 52.5\nlongitude = 13.4\n");
+
+        assert!(matches!(checks.last(), Some(Check::Warning(_))));
+        assert!(!checks.iter().any(|c| matches!(c, Check::Failed(_))));
+    }
+}